@@ -0,0 +1,94 @@
+#[macro_use]
+extern crate criterion;
+extern crate tztail;
+
+use criterion::{Criterion, Throughput};
+use tztail::Converter;
+
+// Number of lines in each corpus
+const LINES: usize = 1000;
+
+// Nginx access log. Every line has a timestamp with an offset.
+fn nginx(i: usize) -> String {
+    format!(
+        "10.0.{}.{} - - [04/Nov/2018:12:{:02}:{:02} +0000] \"GET /api/v1/orders/{} HTTP/1.1\" 200 {} \"-\" \"curl/7.54.0\"\n",
+        i % 256,
+        i % 100,
+        i / 60 % 60,
+        i % 60,
+        i,
+        512 + i
+    )
+}
+
+// Application log with a stack trace after every few lines. Most lines have no timestamp.
+fn application(i: usize) -> String {
+    if i % 4 == 0 {
+        format!(
+            "2018-11-04 12:{:02}:{:02} ERROR [worker-{}] request {} failed: connection reset\n",
+            i / 60 % 60,
+            i % 60,
+            i % 8,
+            i
+        )
+    } else {
+        format!(
+            "    at com.example.orders.Repository.find(Repository.java:{})\n",
+            i
+        )
+    }
+}
+
+// Lines with timestamps in different formats, some with more than one
+fn mixed(i: usize) -> String {
+    match i % 3 {
+        0 => format!(
+            "2018-11-04T12:{:02}:{:02}+0530 upstream responded at 2018-11-04 06:{:02}:{:02} +0000\n",
+            i / 60 % 60,
+            i % 60,
+            i / 60 % 60,
+            i % 60
+        ),
+        1 => format!(
+            "Sun, 04 Nov 2018 12:{:02}:{:02} +0000 cache hit for key {}\n",
+            i / 60 % 60,
+            i % 60,
+            i
+        ),
+        _ => format!("04/Nov/2018:12:{:02}:{:02} haproxy frontend ok\n", i / 60 % 60, i % 60),
+    }
+}
+
+fn corpus(line: fn(usize) -> String) -> Vec<String> {
+    (0..LINES).map(line).collect()
+}
+
+fn bench_convert(c: &mut Criterion) {
+    let converter = Converter::builder()
+        .timezone("Asia/Kolkata")
+        .build()
+        .unwrap();
+
+    let corpora: Vec<(&str, Vec<String>)> = vec![
+        ("nginx", corpus(nginx)),
+        ("application", corpus(application)),
+        ("mixed", corpus(mixed)),
+    ];
+
+    let mut group = c.benchmark_group("convert");
+    for (name, lines) in &corpora {
+        let bytes: usize = lines.iter().map(|line| line.len()).sum();
+        group.throughput(Throughput::Bytes(bytes as u64));
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                for line in lines {
+                    criterion::black_box(converter.convert(line));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_convert);
+criterion_main!(benches);
@@ -1,31 +1,162 @@
 use atty::Stream;
 use clap::ArgMatches;
 use output_formatter::OutputFormatter;
+use reader::{Follow, Start};
+use tztail::{AmbiguityPolicy, Column, Delimiter, InputMode, OutputFormat};
 
 type Result<T> = ::std::result::Result<T, String>;
 
 // To represent command line arguments
 pub struct Args<'a> {
-    pub filename: Option<&'a str>,
+    pub filenames: Vec<&'a str>,
     pub custom_format: Option<&'a str>,
     pub timezone: Option<&'a str>,
-    pub should_follow: bool,
+    pub source_timezones: Vec<&'a str>,
+    pub ambiguity: AmbiguityPolicy,
+    pub output_format: OutputFormat,
+    pub follow: Follow,
+    pub retry: bool,
+    pub start: Start,
+    pub since: Option<&'a str>,
+    pub until: Option<&'a str>,
+    pub merge: bool,
+    pub prefix: bool,
     pub color_choice: ColorChoice,
+    pub on_parse_error: ParseErrorPolicy,
+    pub auto_lock: Option<usize>,
+    pub verbose: bool,
+    pub epochs: Vec<&'a str>,
+    pub presets: Vec<&'a str>,
+    pub uptime: bool,
+    pub boot_time: Option<&'a str>,
+    pub input_mode: InputMode,
 }
 
 impl<'a> Args<'a> {
     // Parses ArgMatches into Args
     pub fn parse(matches: &'a ArgMatches) -> Result<Args<'a>> {
+        let filenames: Vec<&str> = matches
+            .values_of("FILE")
+            .map(|values| values.collect())
+            .unwrap_or_default();
+        let source_timezones: Vec<&str> = matches
+            .values_of("source-timezone")
+            .map(|values| values.collect())
+            .unwrap_or_default();
+
+        // A single source timezone applies to all inputs. Otherwise there has to be one for every input.
+        let inputs = filenames.len().max(1);
+        if source_timezones.len() > 1 && source_timezones.len() != inputs {
+            return Err(format!(
+                "Expected a single source timezone or one for each of the {} inputs, got {}",
+                inputs,
+                source_timezones.len()
+            ));
+        }
+
+        let follow = if matches.is_present("follow-name") {
+            Follow::Name
+        } else if matches.is_present("follow") {
+            Follow::Descriptor
+        } else {
+            Follow::Never
+        };
+        // Reading STDIN blocks until it has a line, which would hold up following the other files
+        if follow != Follow::Never && filenames.len() > 1 && filenames.contains(&"-") {
+            return Err(String::from(
+                "STDIN cannot be followed along with other files, as reading it holds them up",
+            ));
+        }
+
         Ok(Args {
-            filename: matches.value_of("FILE"),
+            filenames,
             custom_format: matches.value_of("format"),
             timezone: matches.value_of("timezone"),
-            should_follow: matches.is_present("follow"),
+            source_timezones,
+            ambiguity: AmbiguityPolicy::new(matches.value_of("ambiguous")),
+            output_format: OutputFormat::new(matches.value_of("output-format"))
+                .map_err(|err| err.to_string())?,
+            follow,
+            retry: matches.is_present("retry") || matches.is_present("follow-name"),
+            start: Start::new(matches.value_of("lines"))?,
+            since: matches.value_of("since"),
+            until: matches.value_of("until"),
+            merge: matches.is_present("merge"),
+            prefix: matches.is_present("prefix"),
             color_choice: ColorChoice::new(matches.value_of("color")),
+            on_parse_error: ParseErrorPolicy::new(matches.value_of("on-parse-error")),
+            auto_lock: match matches.value_of("auto-lock") {
+                Some(lines) => Some(
+                    lines
+                        .parse()
+                        .map_err(|err| format!("Invalid number of lines '{}': {}", lines, err))?,
+                ),
+                None => None,
+            },
+            verbose: matches.is_present("verbose"),
+            epochs: matches
+                .values_of("epoch")
+                .map(|values| values.collect())
+                .unwrap_or_default(),
+            presets: matches
+                .values_of("preset")
+                .map(|values| values.collect())
+                .unwrap_or_default(),
+            uptime: matches.is_present("uptime") || matches.is_present("boot-time"),
+            boot_time: matches.value_of("boot-time"),
+            input_mode: input_mode(matches)?,
         })
     }
 }
 
+// Picks the input mode along with the keys holding datetimes
+fn input_mode(matches: &ArgMatches) -> Result<InputMode> {
+    let keys: Vec<&str> = matches
+        .values_of("key")
+        .map(|values| values.collect())
+        .unwrap_or_default();
+
+    if matches.is_present("json") {
+        Ok(InputMode::json(&keys))
+    } else if matches.is_present("logfmt") {
+        Ok(InputMode::logfmt(&keys))
+    } else if let Some(columns) = matches.values_of("column") {
+        let columns: ::std::result::Result<Vec<Column>, _> = columns.map(Column::new).collect();
+        Ok(InputMode::Delimited(
+            Delimiter::new(matches.value_of("delimiter")).map_err(|err| err.to_string())?,
+            columns.map_err(|err| err.to_string())?,
+        ))
+    } else if !keys.is_empty() {
+        Err(String::from(
+            "--key can only be used with --json or --logfmt",
+        ))
+    } else {
+        Ok(InputMode::Text)
+    }
+}
+
+// ParseErrorPolicy decides what happens when a datetime in the logs cannot be parsed
+// Ignore leaves it unconverted silently
+// Warn leaves it unconverted, reporting the first one and how many there were on exit
+// Fail stops at the line with the error
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ParseErrorPolicy {
+    Ignore,
+    Warn,
+    Fail,
+}
+
+impl ParseErrorPolicy {
+    // Instantiate ParseErrorPolicy based on cli option chosen
+    fn new(choice: Option<&str>) -> ParseErrorPolicy {
+        match choice {
+            Some("ignore") => ParseErrorPolicy::Ignore,
+            Some("fail") => ParseErrorPolicy::Fail,
+            _ => ParseErrorPolicy::Warn,
+        }
+    }
+}
+
 // ColorChoice can be made from the command line
 // Auto is to decide automatically. If auto is selected, and stdout is a tty, it is colored else it is not.
 // Always to force using color
@@ -50,7 +181,7 @@ impl ColorChoice {
     pub fn build_formatter(&self) -> OutputFormatter {
         match self {
             ColorChoice::Auto => {
-                return if atty::is(Stream::Stdout) {
+                if atty::is(Stream::Stdout) {
                     OutputFormatter::colored()
                 } else {
                     OutputFormatter::plain()
@@ -1,169 +1,1081 @@
+use chrono::format::{Item, ParseResult, StrftimeItems};
 use chrono::prelude::*;
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Duration, LocalResult, Offset, TimeZone, Utc};
 use chrono_tz::Tz;
+use delimited::{self, Column, Delimiter};
+use error::{Error, ParseError};
+use field::Field;
 use format::Format;
+use json;
+use logfmt;
+use regex::RegexSet;
+use registry::{Preset, DEFAULT_FORMATS, DEFAULT_PRESETS};
+use std::cmp::Reverse;
+use std::fmt;
 use std::vec::Vec;
 
-// Converter can be used to convert all the datetimes present in a single line
+/// Converter can be used to convert all the datetimes present in a single line.
+/// It is created with a [`ConverterBuilder`](struct.ConverterBuilder.html).
 //
-// timezone represents the target timezone in which output should be printed.
+// timezone represents the target timezone in which output should be printed. Local if not given.
+// source_timezone is the timezone assumed for datetimes without an offset. UTC if not given.
+// ambiguity decides how local times that are ambiguous or non-existent in source_timezone are resolved.
+// output_format decides how the converted datetimes are printed.
 // formats are the list of all formats the log is evaluated against
+// matcher has the regexes of all formats, to find the formats present in a line in a single pass
+// lock is the format learnt from the first lines of the log
+// input_mode decides which parts of a line are searched for datetimes
+// columns are the fields searched in delimited lines. Columns named in the header wait for it.
+// field_epoch is used to convert epochs found in the fields of structured lines
+// boot_time is where the boot time to convert uptimes with comes from, if they are converted
 pub struct Converter {
     formats: Vec<Format>,
+    input_mode: InputMode,
+    columns: Vec<(usize, usize)>,
+    header_pending: bool,
+    field_epoch: Format,
+    matcher: RegexSet,
+    lock: AutoLock,
     timezone: Option<Tz>,
+    source_timezone: Option<Tz>,
+    ambiguity: AmbiguityPolicy,
+    output_format: OutputFormat,
     local: DateTime<Local>,
+    reference_time: Option<DateTime<Utc>>,
+    boot_time: Option<BootTime>,
 }
 
+/// AmbiguityPolicy decides which instant a local time in the source timezone maps to when a DST
+/// transition makes it ambiguous (clocks turned back) or non-existent (clocks turned forward)
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum AmbiguityPolicy {
+    /// Picks the earlier of the two possible instants
+    #[default]
+    Earliest,
+    /// Picks the later of the two possible instants
+    Latest,
+    /// Leaves the datetime unconverted
+    Error,
+}
+
+impl AmbiguityPolicy {
+    /// Instantiate AmbiguityPolicy from its name: `earliest`, `latest` or `error`.
+    /// Defaults to Earliest.
+    pub fn new(choice: Option<&str>) -> AmbiguityPolicy {
+        match choice {
+            Some("latest") => AmbiguityPolicy::Latest,
+            Some("error") => AmbiguityPolicy::Error,
+            _ => AmbiguityPolicy::Earliest,
+        }
+    }
+
+    // Picks one of the candidate instants based on the policy
+    fn pick(self, a: DateTime<Utc>, b: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            AmbiguityPolicy::Earliest => Some(a.min(b)),
+            AmbiguityPolicy::Latest => Some(a.max(b)),
+            AmbiguityPolicy::Error => None,
+        }
+    }
+}
+
+/// OutputFormat decides how a converted datetime is printed
+#[derive(PartialEq, Debug, Clone, Default)]
+pub enum OutputFormat {
+    /// Prints it with the format it was parsed with
+    #[default]
+    Same,
+    /// Prints it as RFC 3339 in the target timezone. Eg. 2014-11-28T17:30:09+05:30
+    Rfc3339,
+    /// Prints it as ISO 8601 in UTC regardless of the target timezone. Eg. 2014-11-28T12:00:09Z
+    IsoUtc,
+    /// Prints it with the given strftime format in the target timezone
+    Custom(String),
+}
+
+impl OutputFormat {
+    /// Instantiate OutputFormat from a preset name: `same`, `rfc3339` or `iso-utc`.
+    /// Anything else is taken as a custom strftime format. Defaults to Same.
+    pub fn new(choice: Option<&str>) -> Result<OutputFormat, Error> {
+        match choice {
+            None | Some("same") => Ok(OutputFormat::Same),
+            Some("rfc3339") => Ok(OutputFormat::Rfc3339),
+            Some("iso-utc") => Ok(OutputFormat::IsoUtc),
+            Some(fmt) => {
+                if StrftimeItems::new(fmt).any(|item| item == Item::Error) {
+                    return Err(Error::InvalidFormat {
+                        format: String::from(fmt),
+                        reason: String::from("not a valid strftime format"),
+                    });
+                }
+                Ok(OutputFormat::Custom(String::from(fmt)))
+            }
+        }
+    }
+
+    // Renders the datetime. `input_fmt` is the format the datetime was parsed with,
+    // and `fraction` is the fraction of seconds it had, if its format has %.f
+    fn render<T: TimeZone>(
+        &self,
+        dt: &DateTime<T>,
+        input_fmt: &str,
+        fraction: Option<Fraction>,
+    ) -> String
+    where
+        T::Offset: fmt::Display,
+    {
+        match self {
+            // %.f is printed with the separator and precision of the input, instead of chrono's
+            OutputFormat::Same => match input_fmt.find("%.f") {
+                Some(i) => {
+                    let fraction = fraction.map(|f| f.render(dt.nanosecond()));
+                    format!(
+                        "{}{}{}",
+                        dt.format(&input_fmt[..i]),
+                        fraction.unwrap_or_default(),
+                        dt.format(&input_fmt[i + 3..])
+                    )
+                }
+                None => dt.format(input_fmt).to_string(),
+            },
+            OutputFormat::Rfc3339 => dt.format("%Y-%m-%dT%H:%M:%S%.f%:z").to_string(),
+            OutputFormat::IsoUtc => dt
+                .with_timezone(&Utc)
+                .format("%Y-%m-%dT%H:%M:%S%.fZ")
+                .to_string(),
+            OutputFormat::Custom(output_fmt) => dt.format(output_fmt).to_string(),
+        }
+    }
+}
+
+// The format uptimes are printed with when the output format is the same as the input's
+const UPTIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+// What comes before the datetime in kernel log lines setting the system clock, which are the anchors
+// of the boot time. Eg. [    1.234567] rtc_cmos 00:00: setting system clock to 2018-11-04T12:13:49 UTC
+const CLOCK_CONTEXT: &str = "setting system clock to ";
+
+// The formats of the datetime the system clock is set to, by newer and older kernels
+const CLOCK_FORMATS: &[&str] = &["%Y-%m-%dT%H:%M:%S %Z", "%Y-%m-%d %H:%M:%S %Z"];
+
+/// The default keys of structured lines holding datetimes
+pub const DEFAULT_KEYS: &[&str] = &["time", "ts", "timestamp", "@timestamp"];
+
+/// InputMode decides which parts of a line are searched for datetimes
+#[derive(PartialEq, Debug, Clone, Default)]
+pub enum InputMode {
+    /// Searches the whole line
+    #[default]
+    Text,
+    /// Searches only the values of the given keys in lines holding a JSON object.
+    /// Keys of nested objects are joined with a dot. Eg. `meta.time`
+    /// Number values are converted as epochs. Lines that are not JSON are left as they are.
+    Json(Vec<String>),
+    /// Searches only the values of the given keys in logfmt lines. Eg. `ts=... level=info msg="..."`
+    /// Values that are epochs are converted as epochs. The rest of the line is left as it is.
+    Logfmt(Vec<String>),
+    /// Searches only the given columns of delimited lines, like CSV or TSV.
+    /// Fields in double quotes can have the delimiter in them.
+    Delimited(Delimiter, Vec<Column>),
+}
+
+impl InputMode {
+    /// Instantiate JSON mode with the given keys, or DEFAULT_KEYS if there are none
+    pub fn json(keys: &[&str]) -> InputMode {
+        InputMode::Json(keys_or_default(keys))
+    }
+
+    /// Instantiate logfmt mode with the given keys, or DEFAULT_KEYS if there are none
+    pub fn logfmt(keys: &[&str]) -> InputMode {
+        InputMode::Logfmt(keys_or_default(keys))
+    }
+}
+
+fn keys_or_default(keys: &[&str]) -> Vec<String> {
+    let keys = if keys.is_empty() { DEFAULT_KEYS } else { keys };
+    keys.iter().map(|key| String::from(*key)).collect()
+}
+
+/// BootTime is where the time a machine booted at is taken from, to convert the uptimes of
+/// kernel logs with. See [`Format::uptime`](struct.Format.html#method.uptime).
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum BootTime {
+    /// The given time
+    Fixed(DateTime<Utc>),
+    /// The time learnt from the last anchor line, which is a kernel log line setting the system clock.
+    /// Eg. `[    1.234567] rtc_cmos 00:00: setting system clock to 2018-11-04T12:13:49 UTC`
+    /// The given time, if any, is used until an anchor line is read.
+    Anchored(Option<DateTime<Utc>>),
+}
+
+impl BootTime {
+    fn time(self) -> Option<DateTime<Utc>> {
+        match self {
+            BootTime::Fixed(time) => Some(time),
+            BootTime::Anchored(time) => time,
+        }
+    }
+}
+
+/// TimedLog is the result of converting a single line
 #[derive(PartialEq, Debug)]
 pub struct TimedLog<'a> {
-    pub original_time: Option<String>,
-    pub target_time: Option<String>,
+    /// The line that was converted
     pub log: &'a str,
+    /// Whether any timestamp was found and converted
     pub converted: bool,
+    /// Every timestamp found in the line in the order they appear
+    pub spans: Vec<TimeSpan>,
+    /// The datetimes that were found but could not be converted
+    pub errors: Vec<ParseError>,
 }
 
-// The default auto-detectable formats supported.
-// Add standard formats here
-// They get converted into Regexes and are validated
-const DEFAULT_FORMATS: &[&str] = &[
-    "%Y-%m-%dT%H:%M:%S%z",      // 2014-11-28T12:00:09+0000
-    "%Y-%m-%d %H:%M:%S%z",      // 2014-11-28 12:00:09+0000
-    "%Y-%m-%dT%H:%M:%S %z",     // 2014-11-28T12:00:09 +0000
-    "%Y-%m-%d %H:%M:%S %z",     // 2014-11-28 12:00:09 +0000
-    "%d/%b/%Y:%H:%M:%S %z",     // 04/Nov/2018:12:13:49 +0000 Nginx
-    "%d/%b/%Y:%H:%M:%S%.3f %z", // 04/Nov/2018:12:13:49.334 +0000 Nginx
-    "%d/%b/%Y:%H:%M:%S",        // 04/Nov/2018:12:13:49 HAProxy
-    "%a, %d %b %Y %H:%M:%S %z", // Fri, 28 Nov 2014 12:00:09 +0000
-    "%Y-%m-%dT%H:%M:%SZ",       // 2014-11-28T12:00:09Z
-    "%Y-%m-%dT%H:%M:%S",        // 2014-11-28T12:00:09
-    "%Y-%m-%d %H:%M:%S",        // 2014-11-28 12:00:09
-];
-
-// Parses timezone from a string
-fn parse_timezone(tz_str: &str) -> Option<Tz> {
-    match tz_str.parse() {
-        Ok(tz) => return Some(tz),
-        Err(err) => {
-            eprintln!(
-                "Using local timezone as given timezone is not valid: {}",
-                err
-            );
-            return None;
-        }
-    };
+impl<'a> TimedLog<'a> {
+    /// The instant of the first timestamp in the line, if any
+    pub fn instant(&self) -> Option<DateTime<Utc>> {
+        self.spans.first().map(|span| span.instant)
+    }
 }
 
-impl Converter {
-    // Public method to create a new Converter
-    // Takes in two optional paramters
-    //
-    // 1. Timezone
-    // 2. Fmt
-    //
-    // If `timezone` is not specified, the system's local timezone is used.
-    // If `fmt` is not specified, the autodetectable default formats are used.
-    pub fn new(tz_str: Option<&str>, fmt: Option<&str>) -> Result<Converter, String> {
-        let timezone = match tz_str {
-            Some(timezone) => parse_timezone(timezone),
-            None => None,
+/// TimeSpan is a single timestamp found in a line along with
+/// its converted representation in the target timezone
+#[derive(PartialEq, Debug)]
+pub struct TimeSpan {
+    /// Start of the byte range of original_time in the line
+    pub start: usize,
+    /// End of the byte range of original_time in the line
+    pub end: usize,
+    /// The timestamp as found in the line
+    pub original_time: String,
+    /// The point in time original_time refers to
+    pub instant: DateTime<Utc>,
+    /// The timestamp converted to the target timezone, printed with the OutputFormat
+    pub target_time: String,
+    /// Whether the AmbiguityPolicy had to pick the instant
+    pub ambiguous: bool,
+}
+
+/// ConverterBuilder configures and creates a Converter.
+/// Timezones are names in the IANA timezone database and formats are strftime formats.
+///
+/// * `timezone` is the target timezone. The system's local timezone if not given.
+/// * `source_timezone` is assumed for datetimes without an offset. UTC if not given.
+/// * `format` adds a format to detect. The default formats and presets are used if none are given.
+/// * `preset` adds the format of a program's logs by name, like a format.
+#[derive(Default, Debug, Clone)]
+pub struct ConverterBuilder {
+    timezone: Option<String>,
+    source_timezone: Option<String>,
+    formats: Vec<String>,
+    presets: Vec<String>,
+    ambiguity: AmbiguityPolicy,
+    output_format: OutputFormat,
+    auto_lock: usize,
+    epochs: Vec<String>,
+    input_mode: InputMode,
+    reference_time: Option<DateTime<Utc>>,
+    boot_time: Option<BootTime>,
+}
+
+impl ConverterBuilder {
+    /// Creates a builder with the defaults
+    pub fn new() -> ConverterBuilder {
+        ConverterBuilder::default()
+    }
+
+    /// Sets the timezone datetimes are converted to
+    pub fn timezone(&mut self, timezone: &str) -> &mut ConverterBuilder {
+        self.timezone = Some(String::from(timezone));
+        self
+    }
+
+    /// Sets the timezone of datetimes without an offset
+    pub fn source_timezone(&mut self, timezone: &str) -> &mut ConverterBuilder {
+        self.source_timezone = Some(String::from(timezone));
+        self
+    }
+
+    /// Adds a format to detect datetimes with, instead of the default formats
+    pub fn format(&mut self, fmt: &str) -> &mut ConverterBuilder {
+        self.formats.push(String::from(fmt));
+        self
+    }
+
+    /// Adds the format of a preset by name, instead of the default formats.
+    /// See [`PRESETS`](constant.PRESETS.html).
+    pub fn preset(&mut self, name: &str) -> &mut ConverterBuilder {
+        self.presets.push(String::from(name));
+        self
+    }
+
+    /// Adds a context after which epoch timestamps are converted, along with the other formats.
+    /// See [`Format::epoch`](struct.Format.html#method.epoch).
+    /// Epochs are printed unchanged with OutputFormat::Same.
+    pub fn epoch(&mut self, context: &str) -> &mut ConverterBuilder {
+        self.epochs.push(String::from(context));
+        self
+    }
+
+    /// Sets how ambiguous or non-existent local times in the source timezone are resolved
+    pub fn ambiguity(&mut self, ambiguity: AmbiguityPolicy) -> &mut ConverterBuilder {
+        self.ambiguity = ambiguity;
+        self
+    }
+
+    /// Sets how converted datetimes are printed
+    pub fn output_format(&mut self, output_format: OutputFormat) -> &mut ConverterBuilder {
+        self.output_format = output_format;
+        self
+    }
+
+    /// Sets the time by which datetimes without a year, like syslog's, were written.
+    /// Each is put in the latest year in which it is not after this time.
+    /// The time each line is converted at, if not given. Eg. the modified time of a file.
+    pub fn reference_time(&mut self, time: DateTime<Utc>) -> &mut ConverterBuilder {
+        self.reference_time = Some(time);
+        self
+    }
+
+    /// Enables converting the uptimes of kernel logs, like `[ 12345.678901]` of dmesg,
+    /// to the time since the machine booted. They are printed as `%Y-%m-%d %H:%M:%S%.f`
+    /// with OutputFormat::Same, with as many digits of seconds as the uptime has.
+    pub fn uptime(&mut self, boot_time: BootTime) -> &mut ConverterBuilder {
+        self.boot_time = Some(boot_time);
+        self
+    }
+
+    /// Sets which parts of a line are searched for datetimes
+    pub fn input_mode(&mut self, input_mode: InputMode) -> &mut ConverterBuilder {
+        self.input_mode = input_mode;
+        self
+    }
+
+    /// Enables locking onto the format found in most of the first `lines` lines given to
+    /// [`Converter::learn`](struct.Converter.html#method.learn). Once locked, only that format
+    /// is looked for, falling back to all formats on lines where it is not found. Other datetimes
+    /// in lines where it is found are left as they are.
+    pub fn auto_lock(&mut self, lines: usize) -> &mut ConverterBuilder {
+        self.auto_lock = lines;
+        self
+    }
+
+    /// Creates the Converter. Fails if a timezone, format or preset is not valid.
+    pub fn build(&self) -> Result<Converter, Error> {
+        let defaults = self.formats.is_empty() && self.presets.is_empty();
+        let formats: Result<Vec<Format>, Error> = if defaults {
+            DEFAULT_FORMATS.iter().map(|f| Format::new(f)).collect()
+        } else {
+            self.formats.iter().map(|f| Format::new(f)).collect()
+        };
+        let presets: Vec<&str> = if defaults {
+            DEFAULT_PRESETS.to_vec()
+        } else {
+            self.presets.iter().map(|name| name.as_str()).collect()
         };
 
-        let formats = match fmt {
-            Some(fmt) => vec![Format::new(fmt)],
-            None => DEFAULT_FORMATS.iter().map(|f| Format::new(f)).collect(),
+        let mut formats = formats?;
+        for name in presets {
+            let preset =
+                Preset::find(name).ok_or_else(|| Error::UnknownPreset(String::from(name)))?;
+            formats.push(Format::with_context(preset.format, preset.context)?);
+        }
+        if self.boot_time.is_some() {
+            formats.push(Format::uptime());
+            for fmt in CLOCK_FORMATS {
+                formats.push(Format::with_context(fmt, CLOCK_CONTEXT)?);
+            }
+        }
+        for context in &self.epochs {
+            formats.push(Format::epoch(context)?);
+        }
+        let matcher = RegexSet::new(formats.iter().map(|f| f.regex().as_str())).map_err(|err| {
+            Error::InvalidFormat {
+                format: self.formats.join(", "),
+                reason: err.to_string(),
+            }
+        })?;
+
+        let (columns, header_pending) = match self.input_mode {
+            InputMode::Delimited(delimiter, ref columns) => (
+                delimited::resolve(columns, delimiter, None)?,
+                columns.iter().any(|column| match column {
+                    Column::Name(_) => true,
+                    Column::Range(_, _) => false,
+                }),
+            ),
+            _ => (vec![], false),
         };
 
         Ok(Converter {
-            formats: formats,
-            timezone: timezone,
+            lock: AutoLock::new(self.auto_lock, formats.len()),
+            formats,
+            matcher,
+            input_mode: self.input_mode.clone(),
+            columns,
+            header_pending,
+            field_epoch: Format::epoch("")?,
+            timezone: parse_timezone(self.timezone.as_ref())?,
+            source_timezone: parse_timezone(self.source_timezone.as_ref())?,
+            ambiguity: self.ambiguity,
+            output_format: self.output_format.clone(),
             local: Local::now(),
+            reference_time: self.reference_time,
+            boot_time: self.boot_time,
         })
     }
+}
+
+// Parses an optional timezone name
+fn parse_timezone(timezone: Option<&String>) -> Result<Option<Tz>, Error> {
+    match timezone {
+        Some(timezone) => timezone
+            .parse()
+            .map(Some)
+            .map_err(|_| Error::InvalidTimezone(timezone.clone())),
+        None => Ok(None),
+    }
+}
 
-    // The method that converts a given string into the target timezone
-    // It also tries to "detect" the source timezone if available, or it will assume UTC
-    // TODO: the formats are looped sequentially. Use RegexSet to parallely match all expressions
-    // TODO: If there is a hit in autodetected formats, prioritize it
-    // TODO: Allow target timezone configurable
+impl Converter {
+    /// Creates a ConverterBuilder to configure a Converter
+    pub fn builder() -> ConverterBuilder {
+        ConverterBuilder::new()
+    }
+
+    /// The target timezone. None means the system's local timezone.
+    pub fn timezone(&self) -> Option<Tz> {
+        self.timezone
+    }
+
+    /// Converts all the datetimes in a given string into the target timezone.
+    /// The offset in a datetime is used when present, or the source timezone is assumed.
+    //
+    // All formats are matched against the whole line at once. Only the formats that matched
+    // are then searched for their positions. When matches of different formats overlap,
+    // the longest match wins.
     pub fn convert<'a>(&self, input: &'a str) -> TimedLog<'a> {
-        for format in &self.formats {
-            match format.find(input) {
-                Some(found) => {
-                    let source_datetime = found.as_str();
-
-                    if format.is_timezone_aware() {
-                        let dt = match DateTime::parse_from_str(source_datetime, format.fmt()) {
-                            Ok(dt) => dt,
-                            Err(err) => {
-                                eprintln!(
-                                    "Error when parsing from string that is datetime aware: {}",
-                                    err
-                                );
-                                return TimedLog {
-                                    converted: false,
-                                    log: input,
-                                    original_time: None,
-                                    target_time: None,
-                                };
-                            }
-                        };
-
-                        let target_datetime = match self.timezone {
-                            Some(tz) => dt.with_timezone(&tz).format(format.fmt()).to_string(),
-                            None => dt
-                                .with_timezone(&self.local.timezone())
-                                .format(format.fmt())
-                                .to_string(),
-                        };
-
-                        return TimedLog {
-                            converted: true,
-                            original_time: Some(String::from(source_datetime)),
-                            target_time: Some(target_datetime),
-                            log: input,
-                        };
-                    } else {
-                        let dt = match Utc.datetime_from_str(source_datetime, format.fmt()) {
-                            Ok(dt) => dt,
-                            Err(err) => {
-                                eprintln!("Error when parsing using UTC: {}", err);
-                                return TimedLog {
-                                    converted: false,
-                                    log: input,
-                                    original_time: None,
-                                    target_time: None,
-                                };
-                            }
-                        };
-
-                        let target_datetime = match self.timezone {
-                            Some(tz) => dt.with_timezone(&tz).format(format.fmt()).to_string(),
-                            None => dt
-                                .with_timezone(&self.local.timezone())
-                                .format(format.fmt())
-                                .to_string(),
-                        };
-
-                        return TimedLog {
-                            converted: true,
-                            original_time: Some(String::from(source_datetime)),
-                            target_time: Some(target_datetime),
-                            log: input,
-                        };
-                    }
+        let mut spans = Vec::new();
+        let mut errors = Vec::new();
+        match self.input_mode {
+            InputMode::Text => self.convert_range(input, 0, input.len(), &mut spans, &mut errors),
+            InputMode::Json(ref keys) => {
+                let quoting = Quoting {
+                    needs_quotes: |_: &str| false,
+                    escape: json::escape,
+                };
+                for field in json::find_values(input, keys).unwrap_or_default() {
+                    self.convert_field(input, &field, &quoting, &mut spans, &mut errors);
+                }
+            }
+            InputMode::Logfmt(ref keys) => {
+                let needs_quotes = |target: &str| {
+                    target.contains(|c: char| c.is_whitespace() || c == '"' || c == '=')
+                };
+                // Quoted values are escaped with backslashes, like in JSON
+                let quoting = Quoting {
+                    needs_quotes,
+                    escape: json::escape,
+                };
+                for field in logfmt::find_values(input, keys) {
+                    self.convert_field(input, &field, &quoting, &mut spans, &mut errors);
                 }
-                None => {
-                    continue;
+            }
+            InputMode::Delimited(delimiter, _) => {
+                let needs_quotes = |target: &str| match delimiter {
+                    Delimiter::Char(c) => target.contains([c, '"']),
+                    Delimiter::Whitespace => {
+                        target.contains(|t: char| t.is_whitespace() || t == '"')
+                    }
+                };
+                let quoting = Quoting {
+                    needs_quotes,
+                    escape: delimited::escape,
+                };
+                for field in delimited::find_values(input, delimiter, &self.columns) {
+                    self.convert_field(input, &field, &quoting, &mut spans, &mut errors);
                 }
             }
         }
 
-        return TimedLog {
-            converted: false,
+        TimedLog {
             log: input,
-            original_time: None,
-            target_time: None,
+            converted: !spans.is_empty(),
+            spans,
+            errors,
+        }
+    }
+
+    // Converts the datetimes in input[start..end]
+    fn convert_range(
+        &self,
+        input: &str,
+        start: usize,
+        end: usize,
+        spans: &mut Vec<TimeSpan>,
+        errors: &mut Vec<ParseError>,
+    ) {
+        for (from, to, i) in self.find_matches(&input[start..end]) {
+            let format = &self.formats[i];
+            match self.convert_match(input, start + from, start + to, format) {
+                Ok(mut span) => {
+                    // An unquoted epoch in a line is quoted when it is no longer a number,
+                    // so that lines like JSON stay valid. Fields are quoted by convert_field.
+                    let bare = !input[..span.start].ends_with('"');
+                    if format.is_epoch()
+                        && self.input_mode == InputMode::Text
+                        && bare
+                        && !is_number(&span.target_time)
+                    {
+                        span.target_time = format!("\"{}\"", span.target_time);
+                    }
+                    spans.push(span)
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+    }
+
+    // Converts the value of a field in a structured line.
+    // A value that is an epoch is converted as one. Other numbers are not datetimes.
+    // An unquoted value is quoted when what it is printed as needs quotes, or is no longer
+    // a number, and what is printed in quotes is escaped, so that the line stays valid.
+    fn convert_field<Q: Fn(&str) -> bool, E: Fn(&str) -> String>(
+        &self,
+        input: &str,
+        field: &Field,
+        quoting: &Quoting<Q, E>,
+        spans: &mut Vec<TimeSpan>,
+        errors: &mut Vec<ParseError>,
+    ) {
+        let first = spans.len();
+        if parse_epoch(&input[field.start..field.end]).is_some() {
+            match self.convert_match(input, field.start, field.end, &self.field_epoch) {
+                Ok(span) => spans.push(span),
+                Err(err) => errors.push(err),
+            }
+        } else if field.string {
+            self.convert_range(input, field.start, field.end, spans, errors);
+        }
+
+        for span in &mut spans[first..] {
+            if field.spread || span.target_time == span.original_time {
+                continue;
+            }
+            if field.quoted {
+                span.target_time = (quoting.escape)(&span.target_time);
+                continue;
+            }
+            let whole = span.start == field.start && span.end == field.end;
+            if whole && (!field.string || (quoting.needs_quotes)(&span.target_time)) {
+                span.target_time = format!("\"{}\"", (quoting.escape)(&span.target_time));
+            }
+        }
+    }
+
+    /// Whether the columns of delimited lines are named, and the header is yet to be read
+    pub fn needs_header(&self) -> bool {
+        self.header_pending
+    }
+
+    /// Reads the header of delimited lines, which is their first line, to find the named columns.
+    /// Fails if a column is not in the header.
+    pub fn read_header(&mut self, header: &str) -> Result<(), Error> {
+        if let InputMode::Delimited(delimiter, ref columns) = self.input_mode {
+            self.columns = delimited::resolve(columns, delimiter, Some(header))?;
+        }
+        self.header_pending = false;
+        Ok(())
+    }
+
+    /// Learns the boot time from an anchor line, which is a kernel log line setting the system clock,
+    /// when uptimes are converted with BootTime::Anchored. The line is the one converted into `log`.
+    /// Other datetimes after an uptime are left alone. Returns the boot time if it is learnt.
+    pub fn read_anchor(&mut self, log: &TimedLog) -> Option<DateTime<Utc>> {
+        match self.boot_time {
+            Some(BootTime::Anchored(_)) => (),
+            _ => return None,
+        }
+
+        let uptime = self.formats.iter().find(|format| format.is_uptime())?;
+        let (start, end) = uptime.find_iter(log.log).next()?;
+        let since_boot = parse_uptime(&log.log[start..end])?;
+        let instant = log
+            .spans
+            .iter()
+            .find(|span| span.start >= end && log.log[..span.start].ends_with(CLOCK_CONTEXT))?
+            .instant;
+
+        let boot_time = instant.checked_sub_signed(since_boot)?;
+        self.boot_time = Some(BootTime::Anchored(Some(boot_time)));
+        Some(boot_time)
+    }
+
+    /// Learns the format of the log from one of its first lines, when auto-lock is enabled.
+    /// Once enough lines are seen, the converter locks onto the format found in most of them.
+    /// Returns the format when it gets locked.
+    pub fn learn(&mut self, input: &str) -> Option<&str> {
+        if self.lock.is_done() {
+            return None;
+        }
+
+        let mut found: Vec<usize> = self.find_matches(input).iter().map(|m| m.2).collect();
+        found.sort();
+        found.dedup();
+        let locked = self.lock.observe(&found);
+        locked.map(move |i| self.formats[i].fmt())
+    }
+
+    /// The format the converter is locked onto, if any
+    pub fn locked_format(&self) -> Option<&str> {
+        self.lock.locked.map(|i| self.formats[i].fmt())
+    }
+
+    // Finds the non overlapping matches in the input, sorted by position, along with the index of their format.
+    // Once locked, only the locked format is looked for. All formats are looked for if it is not found.
+    fn find_matches(&self, input: &str) -> Vec<(usize, usize, usize)> {
+        if let Some(locked) = self.lock.locked {
+            let found = self.candidates(input, Some(locked));
+            if !found.is_empty() {
+                return found;
+            }
+        }
+
+        let candidates = self.candidates(input, self.matcher.matches(input));
+        resolve_overlaps(candidates)
+    }
+
+    // Finds all the matches of the given formats in the input
+    fn candidates<I: IntoIterator<Item = usize>>(
+        &self,
+        input: &str,
+        formats: I,
+    ) -> Vec<(usize, usize, usize)> {
+        let mut candidates = Vec::new();
+        for i in formats {
+            for (start, end) in self.formats[i].find_iter(input) {
+                candidates.push((start, end, i));
+            }
+        }
+        candidates
+    }
+
+    // Converts a single matched datetime at input[start..end] into the target timezone
+    fn convert_match(
+        &self,
+        input: &str,
+        start: usize,
+        end: usize,
+        format: &Format,
+    ) -> Result<TimeSpan, ParseError> {
+        let source_datetime = &input[start..end];
+        let mut ambiguous = false;
+        let error = |reason: String| ParseError {
+            datetime: String::from(source_datetime),
+            reason,
+        };
+
+        // chrono cannot parse Z as an offset, so it is read as a zero offset of the same shape.
+        // It also only reads a fraction of seconds after a ., so the , of log4j is read as one.
+        let zulu = if source_datetime.ends_with('Z') {
+            zero_offset(format.fmt())
+        } else {
+            None
+        };
+        let fraction = if format.is_uptime() {
+            Fraction::of_uptime(source_datetime)
+        } else if format.fmt().contains("%.f") {
+            Fraction::find(source_datetime)
+        } else {
+            None
+        };
+        // Uptimes have no format of their own, so they are printed as a datetime
+        let input_fmt = if format.is_uptime() {
+            UPTIME_FORMAT
+        } else {
+            format.fmt()
+        };
+        let mut parsed = String::from(source_datetime);
+        if let Some(Fraction {
+            position,
+            separator: ',',
+            ..
+        }) = fraction
+        {
+            parsed.replace_range(position..position + 1, ".");
+        }
+        if let Some(zero) = zulu {
+            parsed.pop();
+            parsed.push_str(zero);
+        }
+
+        let dt = if format.is_epoch() {
+            parse_epoch(source_datetime)
+                .ok_or_else(|| error(String::from("out of range for an epoch timestamp")))?
+        } else if format.is_uptime() {
+            let boot_time = self
+                .boot_time
+                .and_then(BootTime::time)
+                .ok_or_else(|| error(String::from("the boot time is not known")))?;
+            parse_uptime(source_datetime)
+                .and_then(|since_boot| boot_time.checked_add_signed(since_boot))
+                .ok_or_else(|| error(String::from("out of range for an uptime")))?
+        } else if let Some((datetime, fmt, name)) = split_zone_name(&parsed, format.fmt()) {
+            let naive = self
+                .parse_naive(datetime, fmt, format.is_yearless())
+                .map_err(|err| error(format!("{} using {}", err, format.fmt())))?;
+
+            let (dt, policy_applied) = self.resolve_zone_name(name, &naive).ok_or_else(|| {
+                error(format!(
+                    "'{}' is not a timezone or the abbreviation of the source timezone",
+                    name
+                ))
+            })?;
+            ambiguous = policy_applied;
+            dt
+        } else if format.is_timezone_aware() {
+            DateTime::parse_from_str(&parsed, format.fmt())
+                .map_err(|err| error(format!("{} using {}", err, format.fmt())))?
+                .with_timezone(&Utc)
+        } else if let Some(source_tz) = self.source_timezone {
+            let naive = self
+                .parse_naive(&parsed, format.fmt(), format.is_yearless())
+                .map_err(|err| error(format!("{} using {}", err, format.fmt())))?;
+
+            let (dt, policy_applied) = self.resolve_local(&source_tz, &naive).ok_or_else(|| {
+                error(format!(
+                    "ambiguous or does not exist in {}",
+                    source_tz.name()
+                ))
+            })?;
+            ambiguous = policy_applied;
+            dt
+        } else {
+            let naive = self
+                .parse_naive(&parsed, format.fmt(), format.is_yearless())
+                .map_err(|err| error(format!("{} using {}", err, format.fmt())))?;
+            Utc.from_utc_datetime(&naive)
         };
+
+        let mut target_time = match self.timezone {
+            // An epoch is the same in every timezone
+            _ if format.is_epoch() && self.output_format == OutputFormat::Same => {
+                String::from(source_datetime)
+            }
+            Some(tz) => self
+                .output_format
+                .render(&dt.with_timezone(&tz), input_fmt, fraction),
+            None => self.output_format.render(
+                &dt.with_timezone(&self.local.timezone()),
+                input_fmt,
+                fraction,
+            ),
+        };
+        // A zero offset is printed back as Z
+        if let (Some(zero), &OutputFormat::Same) = (zulu, &self.output_format) {
+            if target_time.ends_with(zero) {
+                let len = target_time.len() - zero.len();
+                target_time.truncate(len);
+                target_time.push('Z');
+            }
+        }
+
+        Ok(TimeSpan {
+            start,
+            end,
+            original_time: String::from(source_datetime),
+            instant: dt,
+            target_time,
+            ambiguous,
+        })
+    }
+
+    // Parses a datetime without an offset. Datetimes without a year are put in the latest year
+    // in which they are not after the reference time. So while following a log, December
+    // is in the last year and January in this one once the year turns.
+    fn parse_naive(&self, datetime: &str, fmt: &str, yearless: bool) -> ParseResult<NaiveDateTime> {
+        if !yearless {
+            return NaiveDateTime::parse_from_str(datetime, fmt);
+        }
+
+        // A day of slack, as the reference time is not in the source timezone
+        let latest = self.reference_time.unwrap_or_else(Utc::now).naive_utc() + Duration::days(1);
+        let fmt = format!("%Y {}", fmt);
+        let in_year =
+            |year: i32| NaiveDateTime::parse_from_str(&format!("{} {}", year, datetime), &fmt);
+
+        // Feb 29 can be a few years back
+        let years = (latest.year() - 3..=latest.year()).rev();
+        years
+            .map(in_year)
+            .find(|result| result.is_ok_and(|naive| naive <= latest))
+            .unwrap_or_else(|| in_year(latest.year()))
+    }
+
+    // Maps a local time in a timezone given by its name to an instant. chrono-tz knows
+    // names like UTC, GMT or CET. Other abbreviations, like IST, are only known for the
+    // source timezone.
+    fn resolve_zone_name(
+        &self,
+        name: &str,
+        naive: &NaiveDateTime,
+    ) -> Option<(DateTime<Utc>, bool)> {
+        if let Ok(tz) = name.parse::<Tz>() {
+            return self.resolve_local(&tz, naive);
+        }
+
+        let source_tz = self.source_timezone?;
+        self.resolve_local(&source_tz, naive)
+            .filter(|(dt, _)| dt.with_timezone(&source_tz).format("%Z").to_string() == name)
+    }
+
+    // Maps a local time in the source timezone to an instant.
+    // Returns the instant and whether the AmbiguityPolicy had to be applied.
+    fn resolve_local(&self, tz: &Tz, naive: &NaiveDateTime) -> Option<(DateTime<Utc>, bool)> {
+        match tz.from_local_datetime(naive) {
+            LocalResult::Single(dt) => Some((dt.with_timezone(&Utc), false)),
+            LocalResult::Ambiguous(a, b) => self
+                .ambiguity
+                .pick(a.with_timezone(&Utc), b.with_timezone(&Utc))
+                .map(|dt| (dt, true)),
+            LocalResult::None => {
+                // The local time falls in a gap. Interpret it with the offsets in effect
+                // on either side of the transition.
+                let before = tz.offset_from_utc_datetime(&(*naive - Duration::days(1)));
+                let after = tz.offset_from_utc_datetime(&(*naive + Duration::days(1)));
+                let with_offset = |offset: i32| {
+                    Utc.from_utc_datetime(&(*naive - Duration::seconds(i64::from(offset))))
+                };
+
+                self.ambiguity
+                    .pick(
+                        with_offset(before.fix().local_minus_utc()),
+                        with_offset(after.fix().local_minus_utc()),
+                    )
+                    .map(|dt| (dt, true))
+            }
+        }
+    }
+}
+
+// Quoting is how the values of fields in a structured line are quoted. needs_quotes decides
+// if an unquoted value is to be quoted, and escape escapes what is printed in quotes.
+struct Quoting<Q, E> {
+    needs_quotes: Q,
+    escape: E,
+}
+
+// Fraction is the fraction of seconds in a datetime. Eg. ,123 in 12:00:09,123
+// position is where its separator is in the datetime.
+#[derive(PartialEq, Debug, Clone, Copy)]
+struct Fraction {
+    position: usize,
+    separator: char,
+    digits: usize,
+}
+
+impl Fraction {
+    // Finds the fraction right after the seconds, which come after a :
+    fn find(datetime: &str) -> Option<Fraction> {
+        let bytes = datetime.as_bytes();
+        (3..bytes.len())
+            .find(|&i| {
+                (bytes[i] == b'.' || bytes[i] == b',')
+                    && bytes[i - 3] == b':'
+                    && bytes[i - 2].is_ascii_digit()
+                    && bytes[i - 1].is_ascii_digit()
+                    && bytes.get(i + 1).is_some_and(u8::is_ascii_digit)
+            })
+            .map(|position| Fraction {
+                position,
+                separator: char::from(bytes[position]),
+                digits: bytes[position + 1..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_digit())
+                    .count()
+                    .min(9),
+            })
+    }
+
+    // Finds the fraction of the seconds of an uptime. Eg. .678901 in 12345.678901
+    fn of_uptime(uptime: &str) -> Option<Fraction> {
+        let position = uptime.find('.')?;
+        Some(Fraction {
+            position,
+            separator: '.',
+            digits: (uptime.len() - position - 1).min(9),
+        })
+    }
+
+    // Renders the nanoseconds with the same separator and number of digits
+    fn render(self, nanos: u32) -> String {
+        // Leap seconds have nanoseconds beyond a second
+        let nanos = nanos % 1_000_000_000;
+        format!(
+            "{}{:0width$}",
+            self.separator,
+            nanos / 10u32.pow(9 - self.digits as u32),
+            width = self.digits
+        )
+    }
+}
+
+// Splits a trailing timezone name off a datetime whose format ends with %Z, as chrono cannot
+// parse it. Returns the datetime and format without it, and the name.
+fn split_zone_name<'a, 'f>(datetime: &'a str, fmt: &'f str) -> Option<(&'a str, &'f str, &'a str)> {
+    if !fmt.ends_with("%Z") {
+        return None;
+    }
+    let name_start = datetime
+        .trim_end_matches(|c: char| c.is_ascii_uppercase())
+        .len();
+    if name_start == datetime.len() {
+        return None;
+    }
+    Some((
+        &datetime[..name_start],
+        &fmt[..fmt.len() - 2],
+        &datetime[name_start..],
+    ))
+}
+
+// The zero offset in the shape of the offset the format ends with, if any
+fn zero_offset(fmt: &str) -> Option<&'static str> {
+    if fmt.ends_with("%:z") {
+        Some("+00:00")
+    } else if fmt.ends_with("%#z") {
+        Some("+00")
+    } else if fmt.ends_with("%z") {
+        Some("+0000")
+    } else {
+        None
+    }
+}
+
+// Parses an uptime, which is the seconds since boot with a fraction. Eg. 12345.678901
+// The padding before it is left out.
+fn parse_uptime(uptime: &str) -> Option<Duration> {
+    let uptime = uptime.trim_start();
+    let (secs, fraction) = uptime.split_at(uptime.find('.')?);
+    // More would be centuries
+    if secs.len() > 10 {
+        return None;
+    }
+    // The fraction has at most 9 digits that count, so it is cut or padded to nanoseconds
+    let nanos: String = fraction[1..]
+        .chars()
+        .chain("000000000".chars())
+        .take(9)
+        .collect();
+
+    Some(Duration::seconds(secs.parse().ok()?) + Duration::nanoseconds(nanos.parse().ok()?))
+}
+
+// Whether the text is printed as a number, like an epoch
+fn is_number(text: &str) -> bool {
+    !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit() || b == b'.')
+}
+
+// Parses an epoch timestamp. Its unit is decided by the number of digits of the integer part,
+// which is 10 for seconds, 13 for milliseconds, 16 for microseconds and 19 for nanoseconds.
+fn parse_epoch(epoch: &str) -> Option<DateTime<Utc>> {
+    let (integer, fraction) = match epoch.find('.') {
+        Some(i) => (&epoch[..i], &epoch[i + 1..]),
+        None => (epoch, ""),
+    };
+    if !integer.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let value: i64 = integer.parse().ok()?;
+
+    let (secs, nanos) = match integer.len() {
+        10 => {
+            // The fraction has at most 9 digits that count, so it is cut or padded to nanoseconds
+            let nanos = if fraction.is_empty() {
+                0
+            } else {
+                format!("{:0<9.9}", fraction).parse().ok()?
+            };
+            (value, nanos)
+        }
+        13 => (value / 1_000, (value % 1_000) as u32 * 1_000_000),
+        16 => (value / 1_000_000, (value % 1_000_000) as u32 * 1_000),
+        19 => (value / 1_000_000_000, (value % 1_000_000_000) as u32),
+        _ => return None,
+    };
+
+    match Utc.timestamp_opt(secs, nanos) {
+        LocalResult::Single(dt) => Some(dt),
+        _ => None,
+    }
+}
+
+// Resolves overlapping matches by picking the longest match. On a tie, the format listed first wins.
+// The accepted matches are sorted by position.
+fn resolve_overlaps(mut candidates: Vec<(usize, usize, usize)>) -> Vec<(usize, usize, usize)> {
+    // sort is stable, so formats listed first stay first among equal lengths
+    candidates.sort_by_key(|m| Reverse(m.1 - m.0));
+
+    let mut accepted: Vec<(usize, usize, usize)> = Vec::new();
+    for candidate in candidates {
+        let overlaps = accepted
+            .iter()
+            .any(|other| candidate.0 < other.1 && other.0 < candidate.1);
+        if !overlaps {
+            accepted.push(candidate);
+        }
+    }
+
+    accepted.sort_by_key(|m| m.0);
+    accepted
+}
+
+// AutoLock learns the format of a log from its first lines.
+// lines is the number of lines to learn from. Auto-lock is disabled when it is 0.
+// hits counts the lines each format was found in.
+// locked is the index of the format locked onto.
+#[derive(Debug, Clone)]
+struct AutoLock {
+    lines: usize,
+    seen: usize,
+    hits: Vec<usize>,
+    locked: Option<usize>,
+}
+
+impl AutoLock {
+    fn new(lines: usize, formats: usize) -> AutoLock {
+        AutoLock {
+            lines,
+            seen: 0,
+            hits: vec![0; formats],
+            locked: None,
+        }
+    }
+
+    // Whether there is nothing more to learn
+    fn is_done(&self) -> bool {
+        self.seen >= self.lines
+    }
+
+    // Counts the formats found in a line, and locks onto the format found in most lines
+    // once enough lines are seen. On a tie, the format listed first wins.
+    fn observe(&mut self, found: &[usize]) -> Option<usize> {
+        for &i in found {
+            self.hits[i] += 1;
+        }
+        self.seen += 1;
+
+        if !self.is_done() {
+            return None;
+        }
+
+        self.locked = self
+            .hits
+            .iter()
+            .enumerate()
+            .filter(|(_, &hits)| hits > 0)
+            .max_by_key(|&(i, &hits)| (hits, Reverse(i)))
+            .map(|(i, _)| i);
+        self.locked
     }
 }
 
@@ -178,71 +1090,80 @@ fn _chrono(input: &str) -> String {
     };
     let local: DateTime<Local> = Local::now();
     let tz = local.timezone();
-    return dt
-        .with_timezone(&tz)
+    dt.with_timezone(&tz)
         .format("%Y-%m-%d %H:%M:%S %z")
-        .to_string();
+        .to_string()
 }
 
 #[cfg(test)]
 mod converter_tests {
-    use chrono::DateTime;
+    use super::{AmbiguityPolicy, BootTime, OutputFormat};
+    use chrono::{DateTime, Datelike, TimeZone, Utc};
+    use error::Error;
 
     #[test]
     fn test_new() {
-        match super::Converter::new(Some("Random/str"), None) {
-            Ok(_) => assert!(true),
-            Err(_) => assert!(false),
+        match super::Converter::builder().timezone("Random/str").build() {
+            Err(Error::InvalidTimezone(timezone)) => assert_eq!(timezone, "Random/str"),
+            Err(err) => panic!("{}", err),
+            Ok(_) => panic!("expected an invalid timezone"),
         };
 
-        match super::Converter::new(Some("Asia/Kolkata"), None) {
+        match super::Converter::builder().timezone("Asia/Kolkata").build() {
             Ok(c) => {
-                assert!(true);
-                assert_eq!(c.formats.len(), super::DEFAULT_FORMATS.len());
+                assert_eq!(
+                    c.formats.len(),
+                    super::DEFAULT_FORMATS.len() + super::DEFAULT_PRESETS.len()
+                );
             }
-            Err(_) => assert!(false),
+            Err(err) => panic!("{}", err),
         };
 
-        match super::Converter::new(Some("Asia/Kolkata"), Some("%Y-%m-%d %H:%M:%S %z")) {
+        match super::Converter::builder()
+            .timezone("Asia/Kolkata")
+            .format("%Y-%m-%d %H:%M:%S %z")
+            .build()
+        {
             Ok(c) => {
-                assert!(true);
                 assert_eq!(c.formats.len(), 1);
             }
-            Err(_) => assert!(false),
+            Err(err) => panic!("{}", err),
         };
     }
 
     #[test]
     fn test_convert() {
-        use super::TimedLog;
+        use super::{TimeSpan, TimedLog};
 
         struct TestCase<'a> {
             timezone: Option<&'a str>,
+            source_timezone: Option<&'a str>,
+            ambiguity: AmbiguityPolicy,
             format: Option<&'a str>,
             inputs: Vec<&'a str>,
             outputs: Vec<TimedLog<'a>>,
-        };
+        }
 
         fn convert_utc_to_localtimezone(input: &str, format: &str) -> String {
             use chrono::TimeZone;
 
             let local_timezone = super::Local::now().timezone();
-            return super::Utc
+            super::Utc
                 .datetime_from_str(input, format)
                 .unwrap()
                 .with_timezone(&local_timezone)
                 .format(format)
-                .to_string();
-        };
+                .to_string()
+        }
 
         fn convert_to_localtimezone(input: &str, format: &str) -> String {
             let local_timezone = super::Local::now().timezone();
-            return DateTime::parse_from_str(input, format)
+            DateTime::parse_from_str(input, format)
                 .unwrap()
                 .with_timezone(&local_timezone)
                 .format(format)
-                .to_string();
-        };
+                .to_string()
+        }
 
         let local_timezone_case_1 =
             convert_utc_to_localtimezone("2002-10-02 15:00:00", "%Y-%m-%d %H:%M:%S");
@@ -252,17 +1173,21 @@ mod converter_tests {
         let testcases = vec![
             TestCase {
                 timezone: Some("Asia/Kolkata"),
+                source_timezone: None,
+                ambiguity: AmbiguityPolicy::Earliest,
                 format: None,
                 inputs: vec!["A random log without out any time. It should be left untouched"],
                 outputs: vec![TimedLog {
-                    original_time: None,
-                    target_time: None,
-                    converted: false,
                     log: "A random log without out any time. It should be left untouched",
+                    converted: false,
+                    errors: vec![],
+                    spans: vec![],
                 }],
             },
             TestCase {
                 timezone: Some("Asia/Kolkata"),
+                source_timezone: None,
+                ambiguity: AmbiguityPolicy::Earliest,
                 format: Some("%Y-%m-%d %H:%M:%S %z"),
                 inputs: vec![
                     "2018-08-08 10:32:15 +0000",
@@ -272,37 +1197,69 @@ mod converter_tests {
                 outputs: vec![
                     TimedLog {
                         log: "2018-08-08 10:32:15 +0000",
-                        original_time: Some(String::from("2018-08-08 10:32:15 +0000")),
-                        target_time: Some(String::from("2018-08-08 16:02:15 +0530")),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 25,
+                            original_time: String::from("2018-08-08 10:32:15 +0000"),
+                            instant: Utc.ymd(2018, 8, 8).and_hms(10, 32, 15),
+                            target_time: String::from("2018-08-08 16:02:15 +0530"),
+                            ambiguous: false,
+                        }],
                     },
                     TimedLog {
                         log: "2018-03-03 10:32:15 +0700",
-                        original_time: Some(String::from("2018-03-03 10:32:15 +0700")),
-                        target_time: Some(String::from("2018-03-03 09:02:15 +0530")),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 25,
+                            original_time: String::from("2018-03-03 10:32:15 +0700"),
+                            instant: Utc.ymd(2018, 3, 3).and_hms(3, 32, 15),
+                            target_time: String::from("2018-03-03 09:02:15 +0530"),
+                            ambiguous: false,
+                        }],
                     },
                     TimedLog {
                         log: "2018-08-08 10:32:15 -0200",
-                        original_time: Some(String::from("2018-08-08 10:32:15 -0200")),
-                        target_time: Some(String::from("2018-08-08 18:02:15 +0530")),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 25,
+                            original_time: String::from("2018-08-08 10:32:15 -0200"),
+                            instant: Utc.ymd(2018, 8, 8).and_hms(12, 32, 15),
+                            target_time: String::from("2018-08-08 18:02:15 +0530"),
+                            ambiguous: false,
+                        }],
                     },
                 ],
             },
             TestCase {
                 timezone: Some("Asia/Kolkata"),
+                source_timezone: None,
+                ambiguity: AmbiguityPolicy::Earliest,
                 format: Some("%Y-%m-%d %H:%M:%S"),
                 inputs: vec!["2018-11-03 22:39:33 Some random log"],
                 outputs: vec![TimedLog {
                     log: "2018-11-03 22:39:33 Some random log",
-                    original_time: Some(String::from("2018-11-03 22:39:33")),
-                    target_time: Some(String::from("2018-11-04 04:09:33")),
                     converted: true,
+                    errors: vec![],
+                    spans: vec![TimeSpan {
+                        start: 0,
+                        end: 19,
+                        original_time: String::from("2018-11-03 22:39:33"),
+                        instant: Utc.ymd(2018, 11, 3).and_hms(22, 39, 33),
+                        target_time: String::from("2018-11-04 04:09:33"),
+                        ambiguous: false,
+                    }],
                 }],
             },
             TestCase {
                 timezone: Some("Europe/Paris"),
+                source_timezone: None,
+                ambiguity: AmbiguityPolicy::Earliest,
                 format: None,
                 inputs: vec![
                     "Fri, 28 Nov 2014 12:00:09 +0000",
@@ -319,100 +1276,250 @@ mod converter_tests {
                 outputs: vec![
                     TimedLog {
                         log: "Fri, 28 Nov 2014 12:00:09 +0000",
-                        original_time: Some(String::from("Fri, 28 Nov 2014 12:00:09 +0000")),
-                        target_time: Some(String::from("Fri, 28 Nov 2014 13:00:09 +0100")),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 31,
+                            original_time: String::from("Fri, 28 Nov 2014 12:00:09 +0000"),
+                            instant: Utc.ymd(2014, 11, 28).and_hms(12, 0, 9),
+                            target_time: String::from("Fri, 28 Nov 2014 13:00:09 +0100"),
+                            ambiguous: false,
+                        }],
                     },
                     TimedLog {
                         log: "Thu, 27 Nov 2014 01:00:09 +0530",
-                        original_time: Some(String::from("Thu, 27 Nov 2014 01:00:09 +0530")),
-                        target_time: Some(String::from("Wed, 26 Nov 2014 20:30:09 +0100")),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 31,
+                            original_time: String::from("Thu, 27 Nov 2014 01:00:09 +0530"),
+                            instant: Utc.ymd(2014, 11, 26).and_hms(19, 30, 9),
+                            target_time: String::from("Wed, 26 Nov 2014 20:30:09 +0100"),
+                            ambiguous: false,
+                        }],
                     },
                     TimedLog {
                         log: "14/Nov/2018:22:14:27 -0800",
-                        original_time: Some(String::from("14/Nov/2018:22:14:27 -0800")),
-                        target_time: Some(String::from("15/Nov/2018:07:14:27 +0100")),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 26,
+                            original_time: String::from("14/Nov/2018:22:14:27 -0800"),
+                            instant: Utc.ymd(2018, 11, 15).and_hms(6, 14, 27),
+                            target_time: String::from("15/Nov/2018:07:14:27 +0100"),
+                            ambiguous: false,
+                        }],
                     },
                     TimedLog {
                         log: "2014-11-28T12:00:09+0500",
-                        original_time: Some(String::from("2014-11-28T12:00:09+0500")),
-                        target_time: Some(String::from("2014-11-28T08:00:09+0100")),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 24,
+                            original_time: String::from("2014-11-28T12:00:09+0500"),
+                            instant: Utc.ymd(2014, 11, 28).and_hms(7, 0, 9),
+                            target_time: String::from("2014-11-28T08:00:09+0100"),
+                            ambiguous: false,
+                        }],
                     },
                     TimedLog {
                         log: "2014-11-28 12:00:09+0500",
-                        original_time: Some(String::from("2014-11-28 12:00:09+0500")),
-                        target_time: Some(String::from("2014-11-28 08:00:09+0100")),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 24,
+                            original_time: String::from("2014-11-28 12:00:09+0500"),
+                            instant: Utc.ymd(2014, 11, 28).and_hms(7, 0, 9),
+                            target_time: String::from("2014-11-28 08:00:09+0100"),
+                            ambiguous: false,
+                        }],
                     },
                     TimedLog {
                         log: "2014-11-28T12:00:09 +0500",
-                        original_time: Some(String::from("2014-11-28T12:00:09 +0500")),
-                        target_time: Some(String::from("2014-11-28T08:00:09 +0100")),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 25,
+                            original_time: String::from("2014-11-28T12:00:09 +0500"),
+                            instant: Utc.ymd(2014, 11, 28).and_hms(7, 0, 9),
+                            target_time: String::from("2014-11-28T08:00:09 +0100"),
+                            ambiguous: false,
+                        }],
                     },
                     TimedLog {
                         log: "2014-11-28 12:00:09 +0500",
-                        original_time: Some(String::from("2014-11-28 12:00:09 +0500")),
-                        target_time: Some(String::from("2014-11-28 08:00:09 +0100")),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 25,
+                            original_time: String::from("2014-11-28 12:00:09 +0500"),
+                            instant: Utc.ymd(2014, 11, 28).and_hms(7, 0, 9),
+                            target_time: String::from("2014-11-28 08:00:09 +0100"),
+                            ambiguous: false,
+                        }],
                     },
                     TimedLog {
                         log: "04/Nov/2018:12:13:49 +0500 Nginx",
-                        original_time: Some(String::from("04/Nov/2018:12:13:49 +0500")),
-                        target_time: Some(String::from("04/Nov/2018:08:13:49 +0100")),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 26,
+                            original_time: String::from("04/Nov/2018:12:13:49 +0500"),
+                            instant: Utc.ymd(2018, 11, 4).and_hms(7, 13, 49),
+                            target_time: String::from("04/Nov/2018:08:13:49 +0100"),
+                            ambiguous: false,
+                        }],
                     },
                     TimedLog {
                         log: "04/Nov/2018:12:13:49.334 +0500 Nginx",
-                        original_time: Some(String::from("04/Nov/2018:12:13:49.334 +0500")),
-                        target_time: Some(String::from("04/Nov/2018:08:13:49.334 +0100")),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 30,
+                            original_time: String::from("04/Nov/2018:12:13:49.334 +0500"),
+                            instant: Utc.ymd(2018, 11, 4).and_hms_milli(7, 13, 49, 334),
+                            target_time: String::from("04/Nov/2018:08:13:49.334 +0100"),
+                            ambiguous: false,
+                        }],
                     },
                     TimedLog {
                         log: "04/Nov/2018:12:13:49 HAProxy",
-                        original_time: Some(String::from("04/Nov/2018:12:13:49")),
-                        target_time: Some(String::from("04/Nov/2018:13:13:49")),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 20,
+                            original_time: String::from("04/Nov/2018:12:13:49"),
+                            instant: Utc.ymd(2018, 11, 4).and_hms(12, 13, 49),
+                            target_time: String::from("04/Nov/2018:13:13:49"),
+                            ambiguous: false,
+                        }],
+                    },
+                ],
+            },
+            TestCase {
+                timezone: Some("Asia/Kolkata"),
+                source_timezone: None,
+                ambiguity: AmbiguityPolicy::Earliest,
+                format: None,
+                inputs: vec![
+                    "[04/Nov/2018:12:13:49 +0000] upstream at 2018-11-04T12:13:48+0000 took 1s",
+                    "2018-11-04 12:13:49 +0000 retrying request from 2018-11-04 12:13:40",
+                ],
+                outputs: vec![
+                    TimedLog {
+                        log: "[04/Nov/2018:12:13:49 +0000] upstream at 2018-11-04T12:13:48+0000 took 1s",
+                        converted: true,
+                        errors: vec![],
+                        spans: vec![
+                            TimeSpan {
+                                start: 1,
+                                end: 27,
+                                original_time: String::from("04/Nov/2018:12:13:49 +0000"),
+                                instant: Utc.ymd(2018, 11, 4).and_hms(12, 13, 49),
+                                target_time: String::from("04/Nov/2018:17:43:49 +0530"),
+                                ambiguous: false,
+                            },
+                            TimeSpan {
+                                start: 41,
+                                end: 65,
+                                original_time: String::from("2018-11-04T12:13:48+0000"),
+                                instant: Utc.ymd(2018, 11, 4).and_hms(12, 13, 48),
+                                target_time: String::from("2018-11-04T17:43:48+0530"),
+                                ambiguous: false,
+                            },
+                        ],
+                    },
+                    TimedLog {
+                        log: "2018-11-04 12:13:49 +0000 retrying request from 2018-11-04 12:13:40",
+                        converted: true,
+                        errors: vec![],
+                        spans: vec![
+                            TimeSpan {
+                                start: 0,
+                                end: 25,
+                                original_time: String::from("2018-11-04 12:13:49 +0000"),
+                                instant: Utc.ymd(2018, 11, 4).and_hms(12, 13, 49),
+                                target_time: String::from("2018-11-04 17:43:49 +0530"),
+                                ambiguous: false,
+                            },
+                            TimeSpan {
+                                start: 48,
+                                end: 67,
+                                original_time: String::from("2018-11-04 12:13:40"),
+                                instant: Utc.ymd(2018, 11, 4).and_hms(12, 13, 40),
+                                target_time: String::from("2018-11-04 17:43:40"),
+                                ambiguous: false,
+                            },
+                        ],
                     },
                 ],
             },
             TestCase {
                 timezone: None,
+                source_timezone: None,
+                ambiguity: AmbiguityPolicy::Earliest,
                 format: None,
                 inputs: vec!["2002-10-02 15:00:00", "2012-07-24T23:14:29-0700"],
                 outputs: vec![
                     TimedLog {
                         log: "2002-10-02 15:00:00",
-                        original_time: Some(String::from("2002-10-02 15:00:00")),
-                        target_time: Some(String::from(local_timezone_case_1)),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 19,
+                            original_time: String::from("2002-10-02 15:00:00"),
+                            instant: Utc.ymd(2002, 10, 2).and_hms(15, 0, 0),
+                            target_time: local_timezone_case_1,
+                            ambiguous: false,
+                        }],
                     },
                     TimedLog {
                         log: "2012-07-24T23:14:29-0700",
-                        original_time: Some(String::from("2012-07-24T23:14:29-0700")),
-                        target_time: Some(String::from(local_timezone_case_2)),
                         converted: true,
+                        errors: vec![],
+                        spans: vec![TimeSpan {
+                            start: 0,
+                            end: 24,
+                            original_time: String::from("2012-07-24T23:14:29-0700"),
+                            instant: Utc.ymd(2012, 7, 25).and_hms(6, 14, 29),
+                            target_time: local_timezone_case_2,
+                            ambiguous: false,
+                        }],
                     },
                 ],
             },
         ];
 
         for test in testcases {
-            let converter = match super::Converter::new(test.timezone, test.format) {
+            let mut builder = super::Converter::builder();
+            builder.ambiguity(test.ambiguity);
+            if let Some(timezone) = test.timezone {
+                builder.timezone(timezone);
+            }
+            if let Some(source_timezone) = test.source_timezone {
+                builder.source_timezone(source_timezone);
+            }
+            if let Some(format) = test.format {
+                builder.format(format);
+            }
+
+            let converter = match builder.build() {
                 Ok(c) => c,
-                Err(_) => {
-                    assert!(false);
-                    return;
-                }
+                Err(err) => panic!("{}", err),
             };
 
-            for i in 0..test.inputs.len() {
-                let input = test.inputs[i];
-                let expected_output = &test.outputs[i];
-
+            for (input, expected_output) in test.inputs.iter().zip(test.outputs.iter()) {
                 let output = converter.convert(input);
 
                 assert_eq!(output, *expected_output);
@@ -420,6 +1527,814 @@ mod converter_tests {
         }
     }
 
+    #[test]
+    fn test_convert_with_output_format() {
+        let testcases = vec![
+            (None, "2018-11-03 18:39:33"),
+            (Some("same"), "2018-11-03 18:39:33"),
+            (Some("rfc3339"), "2018-11-03T18:39:33-04:00"),
+            (Some("iso-utc"), "2018-11-03T22:39:33Z"),
+            (Some("%d/%m/%Y %H:%M:%S %Z"), "03/11/2018 18:39:33 EDT"),
+        ];
+
+        for (choice, expected) in testcases {
+            let output_format = OutputFormat::new(choice).unwrap();
+            let converter = super::Converter::builder()
+                .timezone("America/New_York")
+                .output_format(output_format)
+                .build()
+                .unwrap();
+
+            let output = converter.convert("2018-11-03 22:39:33 Some random log");
+            assert_eq!(output.spans[0].target_time, expected);
+        }
+
+        let output = super::Converter::builder()
+            .timezone("Asia/Kolkata")
+            .output_format(OutputFormat::Rfc3339)
+            .build()
+            .unwrap()
+            .convert("04/Nov/2018:12:13:49.334 +0000 and 2018-11-04T12:13:49+0000");
+        let targets: Vec<&str> = output
+            .spans
+            .iter()
+            .map(|span| span.target_time.as_str())
+            .collect();
+        assert_eq!(
+            targets,
+            vec!["2018-11-04T17:43:49.334+05:30", "2018-11-04T17:43:49+05:30"]
+        );
+
+        match OutputFormat::new(Some("%Y-%m-%d %Q")) {
+            Err(Error::InvalidFormat { format, .. }) => assert_eq!(format, "%Y-%m-%d %Q"),
+            other => panic!("expected an invalid format, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_convert_with_source_timezone() {
+        struct TestCase<'a> {
+            ambiguity: AmbiguityPolicy,
+            input: &'a str,
+            output: Option<(&'a str, bool)>,
+        }
+
+        let testcases = vec![
+            TestCase {
+                ambiguity: AmbiguityPolicy::Earliest,
+                input: "2018-07-01 10:00:00 summer",
+                output: Some(("2018-07-01 14:00:00", false)),
+            },
+            TestCase {
+                ambiguity: AmbiguityPolicy::Earliest,
+                input: "2018-12-01 10:00:00 winter",
+                output: Some(("2018-12-01 15:00:00", false)),
+            },
+            // Clocks turned back at 02:00 EDT. 01:30 happens twice.
+            TestCase {
+                ambiguity: AmbiguityPolicy::Earliest,
+                input: "2018-11-04 01:30:00 ambiguous",
+                output: Some(("2018-11-04 05:30:00", true)),
+            },
+            TestCase {
+                ambiguity: AmbiguityPolicy::Latest,
+                input: "2018-11-04 01:30:00 ambiguous",
+                output: Some(("2018-11-04 06:30:00", true)),
+            },
+            TestCase {
+                ambiguity: AmbiguityPolicy::Error,
+                input: "2018-11-04 01:30:00 ambiguous",
+                output: None,
+            },
+            // Clocks turned forward at 02:00 EST. 02:30 never happens.
+            TestCase {
+                ambiguity: AmbiguityPolicy::Earliest,
+                input: "2018-03-11 02:30:00 non-existent",
+                output: Some(("2018-03-11 06:30:00", true)),
+            },
+            TestCase {
+                ambiguity: AmbiguityPolicy::Latest,
+                input: "2018-03-11 02:30:00 non-existent",
+                output: Some(("2018-03-11 07:30:00", true)),
+            },
+            TestCase {
+                ambiguity: AmbiguityPolicy::Error,
+                input: "2018-03-11 02:30:00 non-existent",
+                output: None,
+            },
+        ];
+
+        for test in testcases {
+            let converter = super::Converter::builder()
+                .timezone("UTC")
+                .source_timezone("America/New_York")
+                .format("%Y-%m-%d %H:%M:%S")
+                .ambiguity(test.ambiguity)
+                .build()
+                .unwrap();
+
+            let output = converter.convert(test.input);
+            let actual = output
+                .spans
+                .first()
+                .map(|span| (span.target_time.as_str(), span.ambiguous));
+
+            assert_eq!(actual, test.output, "{}", test.input);
+            // Times the policy refuses to resolve are reported instead
+            assert_eq!(output.errors.len(), test.output.is_none() as usize);
+        }
+
+        assert!(super::Converter::builder()
+            .source_timezone("Random/str")
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_convert_epoch() {
+        // Unquoted epochs are quoted when they are no longer a number
+        let testcases = vec![
+            (
+                OutputFormat::Rfc3339,
+                r#"{"ts":1542794400,"msg":"served"}"#,
+                "\"2018-11-21T15:30:00+05:30\"",
+            ),
+            (
+                OutputFormat::Rfc3339,
+                r#"{"ts":1542794400.25}"#,
+                "\"2018-11-21T15:30:00.250+05:30\"",
+            ),
+            (
+                OutputFormat::Rfc3339,
+                r#"{"ts":"1542794400123"}"#,
+                "2018-11-21T15:30:00.123+05:30",
+            ),
+            (
+                OutputFormat::Rfc3339,
+                r#"{"ts":1542794400123456}"#,
+                "\"2018-11-21T15:30:00.123456+05:30\"",
+            ),
+            (
+                OutputFormat::IsoUtc,
+                r#"{"ts":1542794400123456789}"#,
+                "\"2018-11-21T10:00:00.123456789Z\"",
+            ),
+            (
+                OutputFormat::Same,
+                r#"{"ts":1542794400123}"#,
+                "1542794400123",
+            ),
+            (
+                OutputFormat::new(Some("%s")).unwrap(),
+                r#"{"ts":1542794400123}"#,
+                "1542794400",
+            ),
+        ];
+
+        for (output_format, input, expected) in testcases {
+            let converter = super::Converter::builder()
+                .timezone("Asia/Kolkata")
+                .epoch("\"ts\":")
+                .output_format(output_format)
+                .build()
+                .unwrap();
+
+            let output = converter.convert(input);
+            assert_eq!(output.spans.len(), 1, "{}", input);
+            assert_eq!(output.spans[0].target_time, expected, "{}", input);
+            assert_eq!(
+                output.instant().map(|i| i.timestamp()),
+                Some(1542794400),
+                "{}",
+                input
+            );
+        }
+
+        // Other numbers are left alone, while other formats are still found
+        let converter = super::Converter::builder()
+            .timezone("Asia/Kolkata")
+            .epoch("ts=")
+            .build()
+            .unwrap();
+        let output = converter.convert("2018-11-21 10:00:00 id=1542794400 ts=1542794400");
+        let targets: Vec<&str> = output
+            .spans
+            .iter()
+            .map(|span| span.target_time.as_str())
+            .collect();
+        assert_eq!(targets, vec!["2018-11-21 15:30:00", "1542794400"]);
+        assert_eq!(output.spans[1].start, 37);
+    }
+
+    #[test]
+    fn test_convert_json() {
+        let testcases = vec![
+            (
+                OutputFormat::Same,
+                r#"{"time":"2018-11-21 10:00:00","msg":"at 2018-11-21 10:00:00","ts":1542794400}"#,
+                vec!["2018-11-21 15:30:00", "1542794400"],
+            ),
+            (
+                OutputFormat::Rfc3339,
+                r#"{"level":"info","meta":{"time":"2018-11-21T10:00:00Z"},"time":1542794400123}"#,
+                vec![
+                    "2018-11-21T15:30:00+05:30",
+                    "\"2018-11-21T15:30:00.123+05:30\"",
+                ],
+            ),
+            (
+                OutputFormat::Rfc3339,
+                r#"{"ts":"1542794400","time":42,"@timestamp":null}"#,
+                vec!["2018-11-21T15:30:00+05:30"],
+            ),
+            // Digits beyond nanoseconds are left out
+            (
+                OutputFormat::Rfc3339,
+                r#"{"ts":1700000000.1234567891}"#,
+                vec!["\"2023-11-15T03:43:20.123456789+05:30\""],
+            ),
+            // Lines that are not JSON are left as they are
+            (
+                OutputFormat::Same,
+                "2018-11-21 10:00:00 time=1542794400",
+                vec![],
+            ),
+        ];
+
+        for (output_format, input, expected) in testcases {
+            let converter = super::Converter::builder()
+                .timezone("Asia/Kolkata")
+                .input_mode(super::InputMode::json(&[
+                    "time",
+                    "ts",
+                    "@timestamp",
+                    "meta.time",
+                ]))
+                .output_format(output_format)
+                .build()
+                .unwrap();
+
+            let output = converter.convert(input);
+            let targets: Vec<&str> = output
+                .spans
+                .iter()
+                .map(|span| span.target_time.as_str())
+                .collect();
+            assert_eq!(targets, expected, "{}", input);
+        }
+
+        assert_eq!(
+            super::InputMode::json(&[]),
+            super::InputMode::Json(vec![
+                String::from("time"),
+                String::from("ts"),
+                String::from("timestamp"),
+                String::from("@timestamp"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_convert_json_escapes() {
+        let converter = super::Converter::builder()
+            .timezone("Asia/Kolkata")
+            .input_mode(super::InputMode::json(&["ts", "time"]))
+            .output_format(OutputFormat::new(Some("%H\"%M\\%S")).unwrap())
+            .build()
+            .unwrap();
+
+        let output = converter.convert(r#"{"ts":"2018-11-21 10:00:00","time":1542794400}"#);
+        let targets: Vec<&str> = output
+            .spans
+            .iter()
+            .map(|span| span.target_time.as_str())
+            .collect();
+        assert_eq!(targets, vec![r#"15\"30\\00"#, r#""15\"30\\00""#]);
+    }
+
+    #[test]
+    fn test_convert_logfmt() {
+        let testcases = vec![
+            (
+                OutputFormat::Same,
+                r#"ts=2024-01-02T03:04:05Z level=info msg="served at 2024-01-02T03:04:05Z""#,
+                vec!["2024-01-02T08:34:05+05:30"],
+            ),
+            (
+                OutputFormat::Same,
+                r#"time="2024-01-02 03:04:05" epoch=1704164645"#,
+                vec!["2024-01-02 08:34:05", "1704164645"],
+            ),
+            // Values printed with spaces are quoted, unless they already are
+            (
+                OutputFormat::Custom(String::from("%Y-%m-%d %H:%M:%S")),
+                r#"ts=2024-01-02T03:04:05Z time="2024-01-02T03:04:05Z" epoch=1704164645"#,
+                vec![
+                    "\"2024-01-02 08:34:05\"",
+                    "2024-01-02 08:34:05",
+                    "\"2024-01-02 08:34:05\"",
+                ],
+            ),
+            (
+                OutputFormat::Rfc3339,
+                "epoch=1704164645 id=1704164645",
+                vec!["2024-01-02T08:34:05+05:30"],
+            ),
+            // Digits beyond nanoseconds are left out
+            (
+                OutputFormat::Rfc3339,
+                "epoch=1700000000.1234567891",
+                vec!["2023-11-15T03:43:20.123456789+05:30"],
+            ),
+            // Quotes in what is printed are escaped
+            (
+                OutputFormat::Custom(String::from("%H\"%M")),
+                r#"ts=2024-01-02T03:04:05Z time="2024-01-02T03:04:05Z""#,
+                vec![r#""08\"34""#, r#"08\"34"#],
+            ),
+        ];
+
+        for (output_format, input, expected) in testcases {
+            let converter = super::Converter::builder()
+                .timezone("Asia/Kolkata")
+                .input_mode(super::InputMode::logfmt(&["ts", "time", "epoch"]))
+                .output_format(output_format)
+                .build()
+                .unwrap();
+
+            let output = converter.convert(input);
+            let targets: Vec<&str> = output
+                .spans
+                .iter()
+                .map(|span| span.target_time.as_str())
+                .collect();
+            assert_eq!(targets, expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_convert_delimited() {
+        use super::{Column, Delimiter, InputMode};
+
+        let converter = |delimiter, columns, output_format| {
+            super::Converter::builder()
+                .timezone("Asia/Kolkata")
+                .input_mode(InputMode::Delimited(delimiter, columns))
+                .output_format(output_format)
+                .build()
+                .unwrap()
+        };
+        let targets = |log: super::TimedLog| -> Vec<String> {
+            log.spans.into_iter().map(|span| span.target_time).collect()
+        };
+
+        // Only the selected column is converted
+        let csv = converter(
+            Delimiter::Char(','),
+            vec![Column::Range(2, 2)],
+            OutputFormat::Same,
+        );
+        assert_eq!(
+            targets(csv.convert(
+                "2018-11-21 10:00:00,\"2018-11-21 10:00:00\",\"at 2018-11-21 10:00:00\"\n"
+            )),
+            vec!["2018-11-21 15:30:00"]
+        );
+
+        // Unquoted fields are quoted when they have the delimiter
+        let csv = converter(
+            Delimiter::Char(','),
+            vec![Column::Range(1, 1)],
+            OutputFormat::Custom(String::from("%d %b, %Y %H:%M")),
+        );
+        assert_eq!(
+            targets(csv.convert("2018-11-21T10:00:00Z,served")),
+            vec!["\"21 Nov, 2018 15:30\""]
+        );
+
+        // Quotes in what is printed are doubled, in quoted and unquoted fields
+        let csv = converter(
+            Delimiter::Char(','),
+            vec![Column::Range(1, 1), Column::Range(2, 2)],
+            OutputFormat::Custom(String::from("%H\"%M")),
+        );
+        assert_eq!(
+            targets(csv.convert("2018-11-21T10:00:00Z,\"2018-11-21T10:00:00Z\"")),
+            vec!["\"15\"\"30\"", "15\"\"30"]
+        );
+
+        // Datetimes spread over columns are converted as they are
+        let log = converter(
+            Delimiter::Whitespace,
+            vec![Column::Range(1, 2)],
+            OutputFormat::Same,
+        );
+        assert_eq!(
+            targets(log.convert("2018-11-21 10:00:00 web-1 2018-11-21 10:00:00")),
+            vec!["2018-11-21 15:30:00"]
+        );
+
+        // Named columns are found once the header is read
+        let mut tsv = converter(
+            Delimiter::Char('\t'),
+            vec![Column::Name(String::from("created_at"))],
+            OutputFormat::Same,
+        );
+        assert!(tsv.needs_header());
+        assert!(targets(tsv.convert("1\t2018-11-21T10:00:00Z")).is_empty());
+        tsv.read_header("id\tcreated_at\n").unwrap();
+        assert!(!tsv.needs_header());
+        assert_eq!(
+            targets(tsv.convert("1\t2018-11-21T10:00:00Z")),
+            vec!["2018-11-21T15:30:00+05:30"]
+        );
+
+        let mut tsv = converter(
+            Delimiter::Char('\t'),
+            vec![Column::Name(String::from("created_at"))],
+            OutputFormat::Same,
+        );
+        match tsv.read_header("id\tupdated_at") {
+            Err(Error::InvalidColumn { column, .. }) => assert_eq!(column, "created_at"),
+            other => panic!("expected an invalid column, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_convert_rfc3339() {
+        let testcases = vec![
+            (
+                "Asia/Kolkata",
+                "<34>1 2024-01-02T03:04:05.123456+05:30 host app - - msg",
+                "2024-01-02T03:04:05.123456+05:30",
+            ),
+            (
+                "Asia/Kolkata",
+                "<34>1 2024-01-01T21:34:05.123-08:00 host app - - msg",
+                "2024-01-02T11:04:05.123+05:30",
+            ),
+            (
+                "Asia/Kolkata",
+                "2024-01-01T21:34:05.123456789Z app started",
+                "2024-01-02T03:04:05.123456789+05:30",
+            ),
+            (
+                "UTC",
+                "2024-01-02T08:34:05+05:30 app started",
+                "2024-01-02T03:04:05+00:00",
+            ),
+            // Z is printed back as Z
+            (
+                "UTC",
+                "2024-01-02T03:04:05.123Z app started",
+                "2024-01-02T03:04:05.123Z",
+            ),
+            (
+                "UTC",
+                "2024-01-02T03:04:05Z app started",
+                "2024-01-02T03:04:05Z",
+            ),
+            // journald short-iso-precise
+            (
+                "Asia/Kolkata",
+                "2024-01-02T03:04:05.123456-0130 host app[42]: msg",
+                "2024-01-02T10:04:05.123456+0530",
+            ),
+            // A word starting with Z is not an offset
+            (
+                "Asia/Kolkata",
+                "2018-11-04 12:13:49 Zookeeper started",
+                "2018-11-04 17:43:49",
+            ),
+            (
+                "Asia/Kolkata",
+                "2018-11-04T12:13:49Zone started",
+                "2018-11-04T17:43:49",
+            ),
+        ];
+
+        for (timezone, input, expected) in testcases {
+            let converter = super::Converter::builder()
+                .timezone(timezone)
+                .build()
+                .unwrap();
+
+            let output = converter.convert(input);
+            assert_eq!(output.spans.len(), 1, "{}", input);
+            assert_eq!(output.spans[0].target_time, expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_convert_fractions() {
+        let converter = super::Converter::builder()
+            .timezone("Asia/Kolkata")
+            .build()
+            .unwrap();
+
+        let testcases = vec![
+            (
+                "2018-11-03 19:47:20.279044 worker started",
+                "2018-11-04 01:17:20.279044",
+            ),
+            (
+                "2018-11-03 12:00:09,123 INFO [main] started",
+                "2018-11-03 17:30:09,123",
+            ),
+            ("2018-11-03T12:00:09.1 started", "2018-11-03T17:30:09.1"),
+            ("2018-11-03T12:00:09.000 started", "2018-11-03T17:30:09.000"),
+            (
+                "2018-11-03 12:00:09.123456789 +0000 started",
+                "2018-11-03 17:30:09.123456789 +0530",
+            ),
+            (
+                "04/Nov/2018:12:13:49.33 +0000 GET /",
+                "04/Nov/2018:17:43:49.33 +0530",
+            ),
+            (
+                "2018-11-03T12:00:09,5Z started",
+                "2018-11-03T17:30:09,5+05:30",
+            ),
+            // Digits beyond nanoseconds are left out
+            (
+                "2018-11-03T12:00:09.123456789123Z started",
+                "2018-11-03T17:30:09.123456789+05:30",
+            ),
+        ];
+
+        for (input, expected) in testcases {
+            let output = converter.convert(input);
+            assert_eq!(output.spans.len(), 1, "{}", input);
+            assert_eq!(output.spans[0].start, 0, "{}", input);
+            assert_eq!(
+                output.spans[0].original_time.len(),
+                output.spans[0].end,
+                "{}",
+                input
+            );
+            assert_eq!(output.spans[0].target_time, expected, "{}", input);
+        }
+
+        let output = converter.convert("2018-11-03 12:00:09,123 INFO [main] started");
+        assert_eq!(output.spans[0].original_time, "2018-11-03 12:00:09,123");
+        assert_eq!(
+            output.instant().map(|i| i.timestamp_subsec_millis()),
+            Some(123)
+        );
+    }
+
+    #[test]
+    fn test_convert_yearless() {
+        let reference = Utc.ymd(2019, 1, 1).and_hms(1, 0, 0);
+        let converter = super::Converter::builder()
+            .timezone("Asia/Kolkata")
+            .reference_time(reference)
+            .build()
+            .unwrap();
+
+        let testcases = vec![
+            (
+                "Nov  4 12:13:49 host sshd[42]: accepted",
+                "Nov  4 17:43:49",
+                2018,
+            ),
+            (
+                "Dec 31 23:59:59 host cron[1]: rotated",
+                "Jan  1 05:29:59",
+                2018,
+            ),
+            // A day later than the reference is taken as clock skew
+            (
+                "Jan  1 12:30:00 host cron[1]: started",
+                "Jan  1 18:00:00",
+                2019,
+            ),
+            (
+                "Jan  3 00:00:00 host cron[1]: started",
+                "Jan  3 05:30:00",
+                2018,
+            ),
+            // Feb 29 is in the last leap year
+            ("Feb 29 10:00:00 host kernel: leap", "Feb 29 15:30:00", 2016),
+        ];
+
+        for (input, expected, year) in testcases {
+            let output = converter.convert(input);
+            assert_eq!(output.spans.len(), 1, "{}", input);
+            assert_eq!(output.spans[0].target_time, expected, "{}", input);
+            assert_eq!(output.spans[0].instant.year(), year, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_convert_klog() {
+        let reference = Utc.ymd(2019, 1, 1).and_hms(1, 0, 0);
+        let converter = super::Converter::builder()
+            .timezone("Asia/Kolkata")
+            .reference_time(reference)
+            .build()
+            .unwrap();
+
+        let testcases = vec![
+            (
+                "I1103 19:47:20.279044   12345 controller.go:42] synced",
+                vec!["1104 01:17:20.279044"],
+                2018,
+            ),
+            (
+                "E1231 20:00:00.000001       1 reflector.go:7] failed at 2018-12-31 20:00:00",
+                vec!["0101 01:30:00.000001", "2019-01-01 01:30:00"],
+                2018,
+            ),
+            // kubectl logs --prefix
+            (
+                "[pod/etcd-0/etcd] W0101 00:10:00.5 7 wal.go:9] slow",
+                vec!["0101 05:40:00.5"],
+                2019,
+            ),
+            // Without the severity letter it is not a klog header
+            ("1103 19:47:20.279044 not klog", vec![], 0),
+        ];
+
+        for (input, expected, year) in testcases {
+            let output = converter.convert(input);
+            assert!(output.errors.is_empty(), "{}", input);
+            let target_times: Vec<&str> = output
+                .spans
+                .iter()
+                .map(|span| span.target_time.as_str())
+                .collect();
+            assert_eq!(target_times, expected, "{}", input);
+            if let Some(instant) = output.instant() {
+                assert_eq!(instant.year(), year, "{}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_convert_uptime() {
+        let boot_time = Utc.ymd(2018, 11, 4).and_hms(12, 0, 0);
+        let converter = super::Converter::builder()
+            .timezone("Asia/Kolkata")
+            .uptime(BootTime::Fixed(boot_time))
+            .build()
+            .unwrap();
+
+        let testcases = vec![
+            (
+                "[    0.000000] Linux version 4.19.0",
+                "2018-11-04 17:30:00.000000",
+            ),
+            (
+                "<6>[  829.012345] usb 1-1: new device",
+                "2018-11-04 17:43:49.012345",
+            ),
+            ("[90061.5] eth0: link up", "2018-11-05 18:31:01.5"),
+        ];
+
+        for (input, expected) in testcases {
+            let output = converter.convert(input);
+            assert!(output.errors.is_empty(), "{}", input);
+            assert_eq!(output.spans.len(), 1, "{}", input);
+            assert_eq!(output.spans[0].target_time, expected, "{}", input);
+        }
+
+        let converter = super::Converter::builder()
+            .timezone("UTC")
+            .uptime(BootTime::Fixed(boot_time))
+            .output_format(OutputFormat::Rfc3339)
+            .build()
+            .unwrap();
+        assert_eq!(
+            converter.convert("[   60.25] ok").spans[0].target_time,
+            "2018-11-04T12:01:00.250+00:00"
+        );
+    }
+
+    #[test]
+    fn test_read_anchor() {
+        let mut converter = super::Converter::builder()
+            .timezone("Asia/Kolkata")
+            .uptime(BootTime::Anchored(None))
+            .build()
+            .unwrap();
+
+        // Uptimes cannot be converted until the boot time is known
+        let output = converter.convert("[    0.000000] Linux version 4.19.0");
+        assert!(output.spans.is_empty());
+        assert_eq!(output.errors[0].reason, "the boot time is not known");
+
+        let anchor = |converter: &mut super::Converter, line: &str| {
+            let log = converter.convert(line);
+            converter.read_anchor(&log)
+        };
+        assert_eq!(
+            anchor(&mut converter, "[    0.000000] Linux version 4.19.0"),
+            None
+        );
+        assert_eq!(
+            anchor(
+                &mut converter,
+                "[    1.500000] rtc_cmos 00:00: setting system clock to 2018-11-04T12:00:01 UTC (1541332801)"
+            ),
+            Some(Utc.ymd(2018, 11, 4).and_hms_milli(11, 59, 59, 500))
+        );
+        assert_eq!(
+            converter
+                .convert("[    0.000000] Linux version 4.19.0")
+                .spans[0]
+                .target_time,
+            "2018-11-04 17:29:59.500000"
+        );
+        // Other datetimes after an uptime are not anchors
+        assert_eq!(
+            anchor(
+                &mut converter,
+                "[  900.000000] restored backup of 2018-01-01T00:00:00"
+            ),
+            None
+        );
+        // The datetime the clock is set to is converted along with its timezone name
+        let output = converter.convert(
+            "[    1.500000] rtc_cmos 00:00: setting system clock to 2018-11-04 12:00:01 UTC (1541332801)",
+        );
+        let targets: Vec<&str> = output
+            .spans
+            .iter()
+            .map(|span| span.target_time.as_str())
+            .collect();
+        assert_eq!(
+            targets,
+            vec!["2018-11-04 17:30:01.000000", "2018-11-04 17:30:01 IST"]
+        );
+
+        // A fixed boot time is not changed by anchors
+        let mut converter = super::Converter::builder()
+            .uptime(BootTime::Fixed(Utc.ymd(2018, 11, 4).and_hms(12, 0, 0)))
+            .build()
+            .unwrap();
+        assert_eq!(
+            anchor(
+                &mut converter,
+                "[    1.0] setting system clock to 2018-11-04T13:00:00"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_learn() {
+        let mut converter = super::Converter::builder()
+            .timezone("Asia/Kolkata")
+            .auto_lock(3)
+            .build()
+            .unwrap();
+
+        assert_eq!(converter.learn("2018-11-21 10:00:00 started"), None);
+        assert_eq!(converter.learn("  at frame without a timestamp"), None);
+        assert_eq!(converter.locked_format(), None);
+        assert_eq!(
+            converter.learn("2018-11-21 10:00:01 served at 2018-11-21T10:00:01+0000"),
+            Some("%Y-%m-%d %H:%M:%S%.f")
+        );
+        assert_eq!(converter.locked_format(), Some("%Y-%m-%d %H:%M:%S%.f"));
+        // Nothing more is learnt once locked
+        assert_eq!(converter.learn("2018-11-21T10:00:02+0000"), None);
+
+        // Only the locked format is looked for, so other datetimes in the line are left alone
+        let targets = |log: super::TimedLog| -> Vec<String> {
+            log.spans.into_iter().map(|span| span.target_time).collect()
+        };
+        assert_eq!(
+            targets(converter.convert("2018-11-21 10:00:03 served at 2018-11-21T10:00:03+0000")),
+            vec!["2018-11-21 15:30:03"]
+        );
+        // Otherwise all formats are
+        assert_eq!(
+            targets(converter.convert("served at 2018-11-21T10:00:04+0000")),
+            vec!["2018-11-21T15:30:04+0530"]
+        );
+
+        // A format found twice in a line is counted once for it
+        let mut converter = super::Converter::builder().auto_lock(3).build().unwrap();
+        assert_eq!(
+            converter.learn(
+                "2018-11-21 10:00:00 served at 2018-11-21T10:00:00+0000 until 2018-11-21 10:00:01"
+            ),
+            None
+        );
+        assert_eq!(converter.learn("2018-11-21T10:00:02+0000 served"), None);
+        assert_eq!(
+            converter.learn("2018-11-21 10:00:03 started"),
+            Some("%Y-%m-%dT%H:%M:%S%.f%z")
+        );
+
+        // Without auto-lock, nothing is learnt
+        let mut converter = super::Converter::builder().build().unwrap();
+        assert_eq!(converter.learn("2018-11-21 10:00:00 started"), None);
+        assert_eq!(converter.locked_format(), None);
+    }
+
     // #[test]
     // fn test_chrono() {
     //     let input = "2018-08-08 10:10:10 +0000";
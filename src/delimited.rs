@@ -0,0 +1,317 @@
+// Splits delimited lines like CSV, TSV or whitespace separated columns into fields,
+// so that only the selected columns are converted while the rest of the line stays the same.
+
+use error::Error;
+use field::Field;
+
+/// Delimiter separates the columns of a delimited line
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Delimiter {
+    /// A single character. Eg. `,` for CSV or `\t` for TSV
+    Char(char),
+    /// Any run of spaces or tabs
+    Whitespace,
+}
+
+impl Delimiter {
+    /// Instantiate Delimiter from `whitespace`, `tab` or a single character. Defaults to `,`.
+    pub fn new(choice: Option<&str>) -> Result<Delimiter, Error> {
+        match choice {
+            None => Ok(Delimiter::Char(',')),
+            Some("whitespace") => Ok(Delimiter::Whitespace),
+            Some("tab") | Some("\\t") => Ok(Delimiter::Char('\t')),
+            Some(delimiter) => {
+                let mut chars = delimiter.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if c != '"' => Ok(Delimiter::Char(c)),
+                    _ => Err(Error::InvalidDelimiter {
+                        delimiter: String::from(delimiter),
+                        reason: String::from(
+                            "expected a single character other than '\"', tab or whitespace",
+                        ),
+                    }),
+                }
+            }
+        }
+    }
+}
+
+/// Column selects the fields of a delimited line holding a datetime
+#[derive(PartialEq, Debug, Clone)]
+pub enum Column {
+    /// The columns from the first to the last, counting from 1. A single column if they are the same.
+    /// A range is for datetimes spread over many columns, like `2018-11-21 10:00:00` in whitespace
+    /// separated logs.
+    Range(usize, usize),
+    /// The column with the name in the header, which is the first line
+    Name(String),
+}
+
+impl Column {
+    /// Instantiate Column from a number like `3`, a range like `1-2` or a name in the header
+    pub fn new(spec: &str) -> Result<Column, Error> {
+        let error = |reason: &str| Error::InvalidColumn {
+            column: String::from(spec),
+            reason: String::from(reason),
+        };
+        let number = |n: &str| n.parse::<usize>().ok().filter(|&n| n > 0);
+
+        if !spec.starts_with(|c: char| c.is_ascii_digit()) {
+            return Ok(Column::Name(String::from(spec)));
+        }
+
+        let (first, last) = match spec.find('-') {
+            Some(i) => (number(&spec[..i]), number(&spec[i + 1..])),
+            None => (number(spec), number(spec)),
+        };
+        match (first, last) {
+            (Some(first), Some(last)) if first <= last => Ok(Column::Range(first, last)),
+            (Some(_), Some(_)) => Err(error("the range ends before it starts")),
+            _ => Err(error("columns are counted from 1")),
+        }
+    }
+}
+
+// Resolves the columns to ranges of field indices, counting from 0.
+// Names are looked up in the header, if it is given.
+pub fn resolve(
+    columns: &[Column],
+    delimiter: Delimiter,
+    header: Option<&str>,
+) -> Result<Vec<(usize, usize)>, Error> {
+    let names: Vec<&str> = match header {
+        Some(header) => split(header, delimiter)
+            .iter()
+            .map(|field| &header[field.start..field.end])
+            .collect(),
+        None => vec![],
+    };
+
+    let mut resolved = Vec::with_capacity(columns.len());
+    for column in columns {
+        match column {
+            Column::Range(first, last) => resolved.push((first - 1, last - 1)),
+            Column::Name(name) => match names.iter().position(|n| n == name) {
+                Some(i) => resolved.push((i, i)),
+                None if header.is_none() => (),
+                None => {
+                    return Err(Error::InvalidColumn {
+                        column: name.clone(),
+                        reason: String::from("not a name in the header"),
+                    })
+                }
+            },
+        }
+    }
+    Ok(resolved)
+}
+
+// Finds the fields of the given columns in the line.
+// Columns spread over many fields are taken as they are, with the delimiters between them.
+pub fn find_values(line: &str, delimiter: Delimiter, columns: &[(usize, usize)]) -> Vec<Field> {
+    let fields = split(line, delimiter);
+
+    columns
+        .iter()
+        .filter(|&&(_, last)| last < fields.len())
+        .map(|&(first, last)| {
+            if first == last {
+                fields[first]
+            } else {
+                Field {
+                    start: fields[first].start,
+                    end: fields[last].end,
+                    quoted: false,
+                    string: true,
+                    spread: true,
+                }
+            }
+        })
+        .collect()
+}
+
+// Escapes a value to be written into a field in double quotes
+pub fn escape(value: &str) -> String {
+    value.replace('"', "\"\"")
+}
+
+// Splits the line into fields. A field in double quotes can have delimiters in it,
+// and a double quote in it is written twice. The range of a quoted field is within the quotes.
+fn split(line: &str, delimiter: Delimiter) -> Vec<Field> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let bytes = line.as_bytes();
+    let mut encoded = [0; 4];
+    let delimiter_bytes: &[u8] = match delimiter {
+        Delimiter::Char(c) => c.encode_utf8(&mut encoded).as_bytes(),
+        Delimiter::Whitespace => b" ",
+    };
+    let is_delimiter = |pos: usize| match delimiter {
+        Delimiter::Char(_) => bytes[pos..].starts_with(delimiter_bytes),
+        Delimiter::Whitespace => bytes[pos] == b' ' || bytes[pos] == b'\t',
+    };
+
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    if delimiter == Delimiter::Whitespace {
+        while pos < bytes.len() && is_delimiter(pos) {
+            pos += 1;
+        }
+        if pos == bytes.len() {
+            return fields;
+        }
+    }
+
+    loop {
+        let quoted = pos < bytes.len() && bytes[pos] == b'"';
+        let (start, end) = if quoted {
+            let start = pos + 1;
+            pos = start;
+            loop {
+                match bytes.get(pos) {
+                    Some(b'"') if bytes.get(pos + 1) == Some(&b'"') => pos += 2,
+                    Some(b'"') | None => break,
+                    Some(_) => pos += 1,
+                }
+            }
+            let end = pos;
+            // Anything between the closing quote and the delimiter is left out
+            while pos < bytes.len() && !is_delimiter(pos) {
+                pos += 1;
+            }
+            (start, end)
+        } else {
+            let start = pos;
+            while pos < bytes.len() && !is_delimiter(pos) {
+                pos += 1;
+            }
+            (start, pos)
+        };
+
+        fields.push(Field {
+            start,
+            end,
+            quoted,
+            string: true,
+            spread: false,
+        });
+
+        if pos == bytes.len() {
+            return fields;
+        }
+        pos += delimiter_bytes.len();
+        if delimiter == Delimiter::Whitespace {
+            while pos < bytes.len() && is_delimiter(pos) {
+                pos += 1;
+            }
+            if pos == bytes.len() {
+                return fields;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod delimited_tests {
+    use super::{escape, find_values, resolve, split, Column, Delimiter};
+    use error::Error;
+
+    fn fields(line: &str, delimiter: Delimiter) -> Vec<(&str, bool)> {
+        split(line, delimiter)
+            .iter()
+            .map(|field| (&line[field.start..field.end], field.quoted))
+            .collect()
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            fields(
+                "1,\"2018-11-21 10:00:00\",\"said \"\"hi, there\"\"\",,last\r\n",
+                Delimiter::Char(',')
+            ),
+            vec![
+                ("1", false),
+                ("2018-11-21 10:00:00", true),
+                ("said \"\"hi, there\"\"", true),
+                ("", false),
+                ("last", false),
+            ]
+        );
+        assert_eq!(
+            fields("a\t\"b\tc\"\t", Delimiter::Char('\t')),
+            vec![("a", false), ("b\tc", true), ("", false)]
+        );
+        assert_eq!(
+            fields(
+                "  2018-11-21 10:00:00\t \"a b\" c \n",
+                Delimiter::Whitespace
+            ),
+            vec![
+                ("2018-11-21", false),
+                ("10:00:00", false),
+                ("a b", true),
+                ("c", false),
+            ]
+        );
+        assert_eq!(
+            fields("\"unterminated, quote", Delimiter::Char(',')),
+            vec![("unterminated, quote", true)]
+        );
+        assert_eq!(fields("", Delimiter::Whitespace), vec![]);
+    }
+
+    #[test]
+    fn test_find_values() {
+        let line = "2018-11-21 10:00:00 web-1 \"GET /\"";
+        let values: Vec<&str> = find_values(line, Delimiter::Whitespace, &[(0, 1), (3, 3), (5, 6)])
+            .iter()
+            .map(|field| &line[field.start..field.end])
+            .collect();
+        assert_eq!(values, vec!["2018-11-21 10:00:00", "GET /"]);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("10:00"), "10:00");
+        assert_eq!(escape("10\"00\""), "10\"\"00\"\"");
+    }
+
+    #[test]
+    fn test_column() {
+        assert_eq!(Column::new("3").unwrap(), Column::Range(3, 3));
+        assert_eq!(Column::new("1-2").unwrap(), Column::Range(1, 2));
+        assert_eq!(
+            Column::new("created_at").unwrap(),
+            Column::Name(String::from("created_at"))
+        );
+        assert!(Column::new("0").is_err());
+        assert!(Column::new("3-1").is_err());
+        assert!(Column::new("2-").is_err());
+
+        assert_eq!(Delimiter::new(None).unwrap(), Delimiter::Char(','));
+        assert_eq!(Delimiter::new(Some("tab")).unwrap(), Delimiter::Char('\t'));
+        assert_eq!(Delimiter::new(Some(";")).unwrap(), Delimiter::Char(';'));
+        match Delimiter::new(Some(";;")) {
+            Err(Error::InvalidDelimiter { delimiter, .. }) => assert_eq!(delimiter, ";;"),
+            other => panic!("expected an invalid delimiter, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let columns = vec![
+            Column::Range(1, 2),
+            Column::Name(String::from("updated at")),
+        ];
+        assert_eq!(
+            resolve(&columns, Delimiter::Char(','), Some("id,\"updated at\"\n")).unwrap(),
+            vec![(0, 1), (1, 1)]
+        );
+        // Names are skipped until there is a header
+        assert_eq!(
+            resolve(&columns, Delimiter::Char(','), None).unwrap(),
+            vec![(0, 1)]
+        );
+        assert!(resolve(&columns, Delimiter::Char(','), Some("id,created at")).is_err());
+    }
+}
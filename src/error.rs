@@ -0,0 +1,99 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Errors returned by tztail
+#[derive(Debug)]
+pub enum Error {
+    /// The timezone is not a name in the IANA timezone database. Eg. `Asia/Kolkata`
+    InvalidTimezone(String),
+    /// The strftime format cannot be used to parse or print datetimes
+    InvalidFormat {
+        /// The format as given
+        format: String,
+        /// What is wrong with it
+        reason: String,
+    },
+    /// The column of delimited lines cannot be selected
+    InvalidColumn {
+        /// The column as given
+        column: String,
+        /// What is wrong with it
+        reason: String,
+    },
+    /// The delimiter of delimited lines cannot be used to split them
+    InvalidDelimiter {
+        /// The delimiter as given
+        delimiter: String,
+        /// What is wrong with it
+        reason: String,
+    },
+    /// The preset is not one of [`PRESETS`](constant.PRESETS.html)
+    UnknownPreset(String),
+    /// The boot time to convert uptimes with is not known, along with why
+    UnknownBootTime(String),
+    /// Reading the logs failed
+    Io(io::Error),
+    /// A datetime could not be parsed
+    Parse(ParseError),
+}
+
+/// ParseError is a datetime that looked like a timestamp but could not be converted.
+/// It does not stop the rest of the line from being converted.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ParseError {
+    /// The datetime as found in the input
+    pub datetime: String,
+    /// Why it could not be parsed
+    pub reason: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidTimezone(timezone) => write!(f, "Invalid timezone '{}'", timezone),
+            Error::InvalidFormat { format, reason } => {
+                write!(f, "Invalid format '{}': {}", format, reason)
+            }
+            Error::InvalidColumn { column, reason } => {
+                write!(f, "Invalid column '{}': {}", column, reason)
+            }
+            Error::InvalidDelimiter { delimiter, reason } => {
+                write!(f, "Invalid delimiter '{}': {}", delimiter, reason)
+            }
+            Error::UnknownPreset(name) => write!(f, "Unknown preset '{}'", name),
+            Error::UnknownBootTime(reason) => write!(f, "Unknown boot time: {}", reason),
+            Error::Io(err) => write!(f, "Error while reading lines: {}", err),
+            Error::Parse(err) => err.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not parse '{}': {}", self.datetime, self.reason)
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Error {
+        Error::Parse(err)
+    }
+}
@@ -0,0 +1,13 @@
+// Field is the value of a key or column found in a structured line, like JSON, logfmt or CSV.
+// start and end are its byte range in the line, within the quotes if it is quoted.
+// string is false for values that can only be numbers, like numbers in JSON.
+// spread is true for values spread over many columns, which are never quoted or escaped
+// as that would merge the columns.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Field {
+    pub start: usize,
+    pub end: usize,
+    pub quoted: bool,
+    pub string: bool,
+    pub spread: bool,
+}
@@ -1,73 +1,218 @@
-use regex::{Match, Regex};
+use error::Error;
+use regex::{self, Regex};
 
-// Format holds a format and the regex to capture the format from
-// a string. It also hold information on if its timezone aware format
+/// Format holds a strftime format and the regex to capture the format from
+/// a string. It also hold information on if its timezone aware format,
+/// if it has no year, or if it is an epoch timestamp or an uptime.
+//
+// in_context is whether the regex also matches the context before the datetime,
+// which is then captured by the datetime group.
 pub struct Format {
     fmt: String,
     re: Regex,
     timezone_aware: bool,
+    yearless: bool,
+    epoch: bool,
+    uptime: bool,
+    in_context: bool,
 }
 
+// Epoch timestamps in seconds with an optional fraction, milliseconds, microseconds or nanoseconds
+const EPOCH_REGEX: &str = r"\b(?P<datetime>\d{19}|\d{16}|\d{13}|\d{10}(?:\.\d{1,9})?)\b";
+
+// Seconds since boot in brackets at the start of kernel log lines, after an optional <level>.
+// The padding before the seconds is part of the datetime, so that it is replaced along with them.
+const UPTIME_REGEX: &str = r"^(?:<\d+>)?\[(?P<datetime>\s*\d+\.\d+)\]";
+
 impl Format {
-    // Proxies request to re.find
-    pub fn find<'a>(&self, input: &'a str) -> Option<Match<'a>> {
-        return self.re.find(input);
+    /// Finds the byte ranges of all the non overlapping matches of the format in the input.
+    /// For formats with a context, only the datetime is matched and not the context before it.
+    pub fn find_iter<'r, 'a: 'r>(
+        &'r self,
+        input: &'a str,
+    ) -> Box<dyn Iterator<Item = (usize, usize)> + 'r> {
+        if self.in_context {
+            Box::new(
+                self.re
+                    .captures_iter(input)
+                    .filter_map(|c| c.name("datetime"))
+                    .map(|m| (m.start(), m.end())),
+            )
+        } else {
+            Box::new(self.re.find_iter(input).map(|m| (m.start(), m.end())))
+        }
+    }
+
+    /// The regex that captures the format
+    pub fn regex(&self) -> &Regex {
+        &self.re
     }
 
-    // Getter for timezone aware
+    /// Whether the format has an offset or timezone in it
     pub fn is_timezone_aware(&self) -> bool {
-        return self.timezone_aware;
+        self.timezone_aware
+    }
+
+    /// Whether the format has a month and day but no year, like syslog's `%b %e %H:%M:%S`
+    pub fn is_yearless(&self) -> bool {
+        self.yearless
+    }
+
+    /// Whether the format is for epoch timestamps
+    pub fn is_epoch(&self) -> bool {
+        self.epoch
+    }
+
+    /// Whether the format is for uptimes, the seconds since boot of kernel logs
+    pub fn is_uptime(&self) -> bool {
+        self.uptime
     }
 
-    // Getter for fmt
+    /// The strftime format. For epoch formats, it describes the context instead.
     pub fn fmt(&self) -> &str {
-        return &self.fmt;
+        &self.fmt
     }
 
-    // To create a new Format type from a format string
-    pub fn new(fmt: &str) -> Format {
-        let re = create_re(fmt);
+    /// To create a Format for epoch timestamps that follow the given context. Eg. `"ts":`
+    /// The context keeps numbers elsewhere in a line from being taken for timestamps.
+    /// Whitespace and a quote are allowed between the context and the timestamp.
+    ///
+    /// Timestamps of 10, 13, 16 and 19 digits are read as seconds, milliseconds,
+    /// microseconds and nanoseconds respectively. Seconds can have a fraction. Eg. 1700000000.123
+    pub fn epoch(context: &str) -> Result<Format, Error> {
+        let regex_str = format!(r#"{}\s*"?{}"#, regex::escape(context), EPOCH_REGEX);
+        let re = Regex::new(&regex_str).map_err(|err| Error::InvalidFormat {
+            format: String::from(context),
+            reason: err.to_string(),
+        })?;
 
-        return Format {
-            fmt: String::from(fmt),
-            re: re,
-            timezone_aware: format_has_timezone(fmt),
-        };
+        Ok(Format {
+            fmt: format!("epoch after '{}'", context),
+            re,
+            timezone_aware: true,
+            yearless: false,
+            epoch: true,
+            uptime: false,
+            in_context: true,
+        })
     }
-}
 
-// Utility function to create and compile a regex for the given format.
-fn create_re(format: &str) -> Regex {
-    let format_str = String::from(format);
-    let regex_str = FORMAT_TO_REGEX
-        .iter()
-        .fold(format_str, |acc, item| acc.replace(item.0, item.1));
-    return Regex::new(&regex_str).unwrap();
-}
+    /// To create a Format for the uptime of kernel log lines, like `[ 12345.678901]` of dmesg.
+    /// It is the seconds since boot in brackets at the start of a line, after an optional `<level>`.
+    pub fn uptime() -> Format {
+        Format {
+            fmt: String::from("uptime"),
+            // The regex is a constant, so it is known to be valid
+            re: Regex::new(UPTIME_REGEX).unwrap(),
+            timezone_aware: true,
+            yearless: false,
+            epoch: false,
+            uptime: true,
+            in_context: true,
+        }
+    }
+
+    /// To create a Format from a strftime format that only matches after the given context,
+    /// which is a regex. Eg. `^\[` for datetimes in brackets at the start of a line.
+    /// The context keeps other datetimes in a line from being taken for it.
+    pub fn with_context(fmt: &str, context: &str) -> Result<Format, Error> {
+        let format = Format::new(fmt)?;
+        let regex_str = format!("(?:{})(?P<datetime>{})", context, format.re.as_str());
+        let re = Regex::new(&regex_str).map_err(|err| Error::InvalidFormat {
+            format: String::from(context),
+            reason: err.to_string(),
+        })?;
+
+        Ok(Format {
+            re,
+            in_context: true,
+            ..format
+        })
+    }
 
-// Checks if the given format has any timezone specifiers
-fn format_has_timezone(fmt: &str) -> bool {
-    const TIMEZONE_SPECIFIERS: [&str; 4] = ["%Z", "%z", "%:z", "%#z"];
-    for spec in TIMEZONE_SPECIFIERS.iter() {
-        if fmt.contains(spec) {
-            return true;
+    /// To create a new Format type from a strftime format string.
+    /// Fails if the format has a specifier that is unknown or not supported.
+    pub fn new(fmt: &str) -> Result<Format, Error> {
+        let invalid = |reason: String| Error::InvalidFormat {
+            format: String::from(fmt),
+            reason,
+        };
+
+        let mut regex_str = String::new();
+        let mut timezone_aware = false;
+        let mut has_year = false;
+        let mut has_month = false;
+        let mut rest = fmt;
+
+        while let Some(i) = rest.find('%') {
+            regex_str.push_str(&regex::escape(&rest[..i]));
+            rest = &rest[i..];
+
+            // The longest specifier wins, so that %.3f is not read as %.f
+            let (spec, spec_re) = FORMAT_TO_REGEX
+                .iter()
+                .filter(|(spec, _)| rest.starts_with(spec))
+                .max_by_key(|(spec, _)| spec.len())
+                .ok_or_else(|| {
+                    let position = fmt[..fmt.len() - rest.len()].chars().count() + 1;
+                    let spec: String = rest.chars().take(2).collect();
+                    invalid(format!(
+                        "unknown or unsupported specifier '{}' at position {}",
+                        spec, position
+                    ))
+                })?;
+
+            regex_str.push_str(spec_re);
+            timezone_aware |= TIMEZONE_SPECIFIERS.contains(spec);
+            has_year |= YEAR_SPECIFIERS.contains(spec);
+            has_month |= MONTH_SPECIFIERS.contains(spec);
+            rest = &rest[spec.len()..];
         }
+        regex_str.push_str(&regex::escape(rest));
+
+        let re = Regex::new(&regex_str).map_err(|err| invalid(err.to_string()))?;
+
+        Ok(Format {
+            fmt: String::from(fmt),
+            re,
+            timezone_aware,
+            yearless: has_month && !has_year,
+            epoch: false,
+            uptime: false,
+            in_context: false,
+        })
     }
-    return false;
 }
 
-const FORMAT_TO_REGEX: [(&'static str, &'static str); 51] = [
+// Specifiers that carry an offset or timezone
+const TIMEZONE_SPECIFIERS: [&str; 4] = ["%Z", "%z", "%:z", "%#z"];
+
+// Specifiers that carry the year
+const YEAR_SPECIFIERS: [&str; 12] = [
+    "%Y", "%C", "%y", "%G", "%g", "%D", "%x", "%F", "%v", "%c", "%+", "%s",
+];
+
+// Specifiers that carry the month
+const MONTH_SPECIFIERS: [&str; 4] = ["%m", "%b", "%B", "%h"];
+
+const FORMAT_TO_REGEX: [(&str, &str); 53] = [
     // Date Specifiers
     ("%Y", r"\d{4}"),
     ("%C", r"\d{2}"),
     ("%y", r"\d{2}"),
     ("%m", r"\d{2}"),
-    ("%b", r"\w{3}"),
+    // Month names are spelled out, so that other words are not taken for them
+    (
+        "%b",
+        r"(?i:jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec)",
+    ),
     ("%B", r"\w+"),
-    ("%h", r"\w{3}"),
+    (
+        "%h",
+        r"(?i:jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec)",
+    ),
     ("%d", r"\d{2}"),
-    ("%e", r"\d+"),
-    ("%e", r"\d+"),
+    ("%e", r"[ \d]?\d"),
     ("%a", r"\w{3}"),
     ("%A", r"\w+"),
     ("%w", r"\d"),
@@ -87,15 +232,16 @@ const FORMAT_TO_REGEX: [(&'static str, &'static str); 51] = [
     ("%k", r"\d+"),
     ("%I", r"\d{2}"),
     ("%l", r"\d{1,2}"),
-    ("%P", r"[ap]m)"),
-    ("%p", r"[AP]M)"),
+    ("%P", r"[ap]m"),
+    ("%p", r"[AP]M"),
     ("%M", r"\d{2}"),
     ("%S", r"\d{2}"),
     ("%f", r"\d+"),
     ("%3f", r"\d{3}"),
     ("%6f", r"\d{6}"),
     ("%9f", r"\d{9}"),
-    ("%.f", r"\.\d+"),
+    // Digits beyond nanoseconds are matched, but left out when parsing
+    ("%.f", r"(?:[\.,]\d+)?"),
     ("%.3f", r"\.\d{3}"),
     ("%.6f", r"\.\d{6}"),
     ("%.9f", r"\.\d{9}"),
@@ -105,24 +251,31 @@ const FORMAT_TO_REGEX: [(&'static str, &'static str); 51] = [
     ("%r", r"\d{2}:\d{2}:\d{2} [AP]M"),
     // Timezone Specifiers
     ("%Z", r"[A-Z]+"),
-    ("%z", r"[\+\-]\d{4}"),
-    ("%:z", r"\+\d{2}:\d{2}"),
-    ("%#z", r"\+\d{2,4}"),
+    // Z is a whole word, so that words starting with it are not taken for an offset
+    ("%z", r"(?:[\+\-]\d{4}|Z\b)"),
+    ("%:z", r"(?:[\+\-]\d{2}:\d{2}|Z\b)"),
+    ("%#z", r"(?:[\+\-]\d{2}(?::?\d{2})?|Z\b)"),
     //Date & Time Specifiers
     ("%c", r"\w{3} \w{3} \d+ \d{2}:\d{2}:\d{2} \d{4}"),
     (
         "%+",
-        r"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}.\d+\+\d{2}:\d{2}",
+        r"\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(?:\.\d+)?(?:[\+\-]\d{2}:\d{2}|Z\b)",
     ),
     ("%s", r"\d+"),
+    // Special Specifiers
+    ("%t", r"\t"),
+    ("%n", r"\n"),
+    ("%%", r"%"),
 ];
 
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
 mod format_tests {
+    use error::Error;
     use format::Format;
     #[test]
     fn test_new() {
-        let fmt = Format::new("%Y-%m-%d %H:%M:%S");
+        let fmt = Format::new("%Y-%m-%d %H:%M:%S").unwrap();
 
         assert!(!fmt.is_timezone_aware());
 
@@ -150,7 +303,7 @@ mod format_tests {
 
     #[test]
     fn test_new_with_timezone() {
-        let fmt = Format::new("%Y-%m-%d %H:%M:%S %z");
+        let fmt = Format::new("%Y-%m-%d %H:%M:%S %z").unwrap();
 
         assert!(fmt.is_timezone_aware());
         let valid_str = "2019-08-08 10:20:24 +0000";
@@ -163,7 +316,7 @@ mod format_tests {
             None => assert!(false),
         }
 
-        let fmt = Format::new("%Y-%m-%d %H:%M:%S %Z");
+        let fmt = Format::new("%Y-%m-%d %H:%M:%S %Z").unwrap();
         assert!(fmt.timezone_aware);
         match fmt.re.find("2019-08-08 10:20:24 IST") {
             Some(found) => {
@@ -172,4 +325,169 @@ mod format_tests {
             None => assert!(false),
         }
     }
+
+    #[test]
+    fn test_new_escapes_literals() {
+        let fmt = Format::new("[%d/%b/%Y:%H:%M:%S.%3f] (%p)").unwrap();
+        let input = "GET [04/Nov/2018:12:13:49.334] (PM) took 1s";
+
+        match fmt.re.find(input) {
+            Some(found) => assert_eq!(found.as_str(), "[04/Nov/2018:12:13:49.334] (PM)"),
+            None => panic!("expected a match"),
+        }
+
+        // . is matched literally, not as any character
+        assert!(fmt.re.find("[04/Nov/2018:12:13:49x334] (PM)").is_none());
+
+        let fmt = Format::new("%Y-%m-%d %H:%M:%S%.3f %P 100%%").unwrap();
+        assert!(!fmt.is_yearless());
+        assert!(!fmt.is_timezone_aware());
+        assert!(fmt.re.is_match("2018-11-04 12:13:49.334 pm 100%"));
+        assert!(!fmt.re.is_match("2018-11-04 12:13:49.33 pm 100%"));
+    }
+
+    #[test]
+    fn test_new_rejects_unknown_specifiers() {
+        let testcases = vec![
+            (
+                "%Y-%m-%d %Q",
+                "unknown or unsupported specifier '%Q' at position 10",
+            ),
+            (
+                "[%Y] %",
+                "unknown or unsupported specifier '%' at position 6",
+            ),
+            (
+                "%Y-%m-%d %-H",
+                "unknown or unsupported specifier '%-' at position 10",
+            ),
+        ];
+
+        for (input, expected) in testcases {
+            match Format::new(input) {
+                Err(Error::InvalidFormat { format, reason }) => {
+                    assert_eq!(format, input);
+                    assert_eq!(reason, expected);
+                }
+                Err(err) => panic!("unexpected error: {}", err),
+                Ok(_) => panic!("expected {} to be invalid", input),
+            }
+        }
+    }
+
+    #[test]
+    fn test_new_with_offsets() {
+        let testcases = vec![
+            (
+                "%H:%M:%S%z",
+                vec!["10:20:24+0530", "10:20:24-0800", "10:20:24Z"],
+            ),
+            (
+                "%H:%M:%S%:z",
+                vec!["10:20:24+05:30", "10:20:24-08:00", "10:20:24Z"],
+            ),
+            (
+                "%H:%M:%S%#z",
+                vec!["10:20:24+05", "10:20:24-0800", "10:20:24Z"],
+            ),
+            (
+                "%H:%M:%S%.f",
+                vec!["10:20:24", "10:20:24.1", "10:20:24.123456789"],
+            ),
+            (
+                "%+",
+                vec![
+                    "2019-08-08T10:20:24+05:30",
+                    "2019-08-08T10:20:24.123-08:00",
+                    "2019-08-08T10:20:24Z",
+                ],
+            ),
+        ];
+
+        for (fmt, inputs) in testcases {
+            let format = Format::new(fmt).unwrap();
+            for input in inputs {
+                let found = format.re.find(input).map(|m| m.as_str());
+                assert_eq!(found, Some(input), "{} in {}", fmt, input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_new_yearless() {
+        let fmt = Format::new("%b %e %H:%M:%S").unwrap();
+        assert!(fmt.is_yearless());
+
+        let found: Vec<&str> = fmt
+            .re
+            .find_iter("Nov  4 12:13:49 host sshd[42]: seen Nov 14 12:13:49")
+            .map(|m| m.as_str())
+            .collect();
+        assert_eq!(found, vec!["Nov  4 12:13:49", "Nov 14 12:13:49"]);
+
+        // Times alone have no month to infer a year for
+        assert!(!Format::new("%H:%M:%S").unwrap().is_yearless());
+    }
+
+    #[test]
+    fn test_with_context() {
+        let fmt = Format::with_context("%Y-%m-%d %H:%M:%S", r"^\[").unwrap();
+        let found: Vec<(usize, usize)> = fmt
+            .find_iter("[2018-11-04 12:13:49] retry at 2018-11-04 12:14:49")
+            .collect();
+        assert_eq!(found, vec![(1, 20)]);
+        assert!(!fmt.is_timezone_aware());
+
+        match Format::with_context("%Y-%m-%d", "(") {
+            Err(Error::InvalidFormat { format, .. }) => assert_eq!(format, "("),
+            Err(err) => panic!("{}", err),
+            Ok(_) => panic!("expected an invalid context"),
+        }
+    }
+
+    #[test]
+    fn test_uptime() {
+        let fmt = Format::uptime();
+        assert!(fmt.is_uptime());
+
+        let testcases = vec![
+            ("[    0.000000] Linux version 4.19.0", Some("    0.000000")),
+            (
+                "<6>[12345.678901] usb 1-1: new device",
+                Some("12345.678901"),
+            ),
+            ("[ 42.5] eth0: link up", Some(" 42.5")),
+            // Brackets elsewhere or without a fraction are not uptimes
+            ("kernel: [    0.000000] Linux version", None),
+            ("[12345] worker started", None),
+        ];
+
+        for (input, expected) in testcases {
+            let found: Vec<&str> = fmt.find_iter(input).map(|(s, e)| &input[s..e]).collect();
+            assert_eq!(found, expected.into_iter().collect::<Vec<_>>(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_epoch() {
+        let fmt = Format::epoch("\"ts\":").unwrap();
+        assert!(fmt.is_epoch());
+
+        let testcases = vec![
+            (r#"{"ts":1700000000,"msg":"ok"}"#, Some("1700000000")),
+            (r#"{"ts": 1700000000.123}"#, Some("1700000000.123")),
+            (r#"{"ts":"1700000000123"}"#, Some("1700000000123")),
+            (r#"{"ts":1700000000123456}"#, Some("1700000000123456")),
+            (r#"{"ts":1700000000123456789}"#, Some("1700000000123456789")),
+            // Numbers without the context or of other lengths are left alone
+            (r#"{"id":1700000000}"#, None),
+            (r#"{"ts":17000000001}"#, None),
+            (r#"{"ts":170000000}"#, None),
+        ];
+
+        for (input, expected) in testcases {
+            let found: Vec<&str> = fmt.find_iter(input).map(|(s, e)| &input[s..e]).collect();
+            assert_eq!(found, expected.into_iter().collect::<Vec<_>>(), "{}", input);
+        }
+    }
 }
@@ -0,0 +1,301 @@
+// A scanner that finds the values of keys in a line holding a JSON object.
+// It only finds where values are, so that they can be rewritten in place
+// while the rest of the line stays byte for byte the same.
+
+use field::Field;
+
+// Finds the string and number values of the given keys in the line.
+// Keys of nested objects are joined with a dot. Eg. meta.time
+// Returns None if the line is not a JSON object.
+pub fn find_values(line: &str, keys: &[String]) -> Option<Vec<Field>> {
+    let mut scanner = Scanner {
+        bytes: line.as_bytes(),
+        pos: 0,
+        keys,
+        path: String::new(),
+        values: Vec::new(),
+    };
+
+    scanner.skip_whitespace();
+    if scanner.peek() != Some(b'{') {
+        return None;
+    }
+    scanner.object()?;
+    scanner.skip_whitespace();
+
+    if scanner.pos == scanner.bytes.len() {
+        Some(scanner.values)
+    } else {
+        None
+    }
+}
+
+// Scanner walks the line keeping track of the path of keys to the current value.
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    keys: &'a [String],
+    path: String,
+    values: Vec<Field>,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\r') | Some(b'\n') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn object(&mut self) -> Option<()> {
+        self.expect(b'{')?;
+        self.skip_whitespace();
+        if self.expect(b'}').is_some() {
+            return Some(());
+        }
+
+        loop {
+            self.skip_whitespace();
+            let (start, end) = self.string()?;
+            let key = unescape(&self.bytes[start..end]);
+            self.skip_whitespace();
+            self.expect(b':')?;
+            self.skip_whitespace();
+
+            let parent = self.path.len();
+            if parent > 0 {
+                self.path.push('.');
+            }
+            self.path.push_str(&key);
+            self.value()?;
+            self.path.truncate(parent);
+
+            self.skip_whitespace();
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b'}' => {
+                    self.pos += 1;
+                    return Some(());
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn array(&mut self) -> Option<()> {
+        self.expect(b'[')?;
+        self.skip_whitespace();
+        if self.expect(b']').is_some() {
+            return Some(());
+        }
+
+        // Values in arrays have no key, so none of them are picked
+        let path = self.path.clone();
+        self.path.push_str("[]");
+        loop {
+            self.skip_whitespace();
+            self.value()?;
+            self.skip_whitespace();
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b']' => {
+                    self.pos += 1;
+                    self.path = path;
+                    return Some(());
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn value(&mut self) -> Option<()> {
+        let picked = self.keys.contains(&self.path);
+
+        match self.peek()? {
+            b'{' => self.object(),
+            b'[' => self.array(),
+            b'"' => {
+                let (start, end) = self.string()?;
+                if picked {
+                    self.values.push(Field {
+                        start,
+                        end,
+                        quoted: true,
+                        string: true,
+                        spread: false,
+                    });
+                }
+                Some(())
+            }
+            b'-' | b'0'..=b'9' => {
+                let (start, end) = self.number()?;
+                if picked {
+                    self.values.push(Field {
+                        start,
+                        end,
+                        quoted: false,
+                        string: false,
+                        spread: false,
+                    });
+                }
+                Some(())
+            }
+            b't' => self.literal(b"true"),
+            b'f' => self.literal(b"false"),
+            b'n' => self.literal(b"null"),
+            _ => None,
+        }
+    }
+
+    // Returns the range of the string within the quotes
+    fn string(&mut self) -> Option<(usize, usize)> {
+        self.expect(b'"')?;
+        let start = self.pos;
+        loop {
+            match self.peek()? {
+                b'"' => break,
+                b'\\' => self.pos += 2,
+                _ => self.pos += 1,
+            }
+        }
+        let end = self.pos;
+        self.pos += 1;
+        Some((start, end))
+    }
+
+    fn number(&mut self) -> Option<(usize, usize)> {
+        let start = self.pos;
+        while let Some(b'-') | Some(b'+') | Some(b'.') | Some(b'e') | Some(b'E')
+        | Some(b'0'..=b'9') = self.peek()
+        {
+            self.pos += 1;
+        }
+        Some((start, self.pos))
+    }
+
+    fn literal(&mut self, literal: &[u8]) -> Option<()> {
+        if self.bytes[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Some(())
+        } else {
+            None
+        }
+    }
+}
+
+// Escapes a value to be written into a JSON string
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Unescapes the common escapes in keys. Unicode escapes are left as they are.
+fn unescape(bytes: &[u8]) -> String {
+    let raw = String::from_utf8_lossy(bytes);
+    if !raw.contains('\\') {
+        return raw.into_owned();
+    }
+
+    let mut key = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            key.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => key.push('\n'),
+            Some('t') => key.push('\t'),
+            Some('r') => key.push('\r'),
+            Some('b') => key.push('\u{8}'),
+            Some('f') => key.push('\u{c}'),
+            Some('u') => key.push_str("\\u"),
+            Some(other) => key.push(other),
+            None => key.push('\\'),
+        }
+    }
+    key
+}
+
+#[cfg(test)]
+mod json_tests {
+    use super::{escape, find_values};
+
+    fn values<'a>(line: &'a str, keys: &[&str]) -> Option<Vec<&'a str>> {
+        let keys: Vec<String> = keys.iter().map(|key| String::from(*key)).collect();
+        find_values(line, &keys).map(|values| {
+            values
+                .iter()
+                .map(|value| &line[value.start..value.end])
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_find_values() {
+        let line = r#"{"time":"2018-11-21T10:00:00Z","msg":"at 2018-11-21 10:00:00","ts":1542794400,"meta":{"time":"10:00","tags":["time"]},"ok":true,"n":null}"#;
+
+        assert_eq!(
+            values(line, &["time", "ts"]),
+            Some(vec!["2018-11-21T10:00:00Z", "1542794400"])
+        );
+        assert_eq!(values(line, &["meta.time"]), Some(vec!["10:00"]));
+        // Objects, arrays and missing keys have nothing to convert
+        assert_eq!(values(line, &["meta", "meta.tags", "level"]), Some(vec![]));
+
+        assert_eq!(
+            values(
+                r#" { "a\"b" : "x\"y" , "c.d": [1, {"e": 2}], "e": -1.5e3 } "#,
+                &["a\"b", "c.d", "e"]
+            ),
+            Some(vec![r#"x\"y"#, "-1.5e3"])
+        );
+        assert_eq!(values("{}", &["time"]), Some(vec![]));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("15:30"), "15:30");
+        assert_eq!(escape("15\"30\\ \t\u{1}"), "15\\\"30\\\\ \\t\\u0001");
+    }
+
+    #[test]
+    fn test_find_values_of_invalid_lines() {
+        let testcases = vec![
+            "2018-11-21 10:00:00 not json",
+            r#"["time", "2018-11-21"]"#,
+            r#"{"time":"2018-11-21T10:00:00Z""#,
+            r#"{"time":"2018-11-21T10:00:00Z"} trailing"#,
+            r#"{"time" "2018-11-21T10:00:00Z"}"#,
+            r#"{"time":"2018-11-21T10:00:00Z",}"#,
+            r#"{"time":tru}"#,
+        ];
+
+        for line in testcases {
+            assert_eq!(values(line, &["time"]), None, "{}", line);
+        }
+    }
+}
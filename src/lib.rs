@@ -0,0 +1,42 @@
+//! tztail (TimeZoneTAIL) finds the datetimes in log lines and converts them to the timezone you want.
+//!
+//! A [`Converter`](struct.Converter.html) is configured with a
+//! [`ConverterBuilder`](struct.ConverterBuilder.html) and converts one line at a time
+//! into a [`TimedLog`](struct.TimedLog.html), which holds every datetime found in the line
+//! along with its converted form.
+//!
+//! ```
+//! extern crate tztail;
+//!
+//! let converter = tztail::Converter::builder()
+//!     .timezone("Asia/Kolkata")
+//!     .source_timezone("UTC")
+//!     .build()
+//!     .unwrap();
+//!
+//! let log = converter.convert("2018-11-21 10:00:00 request served");
+//! assert_eq!(log.spans[0].target_time, "2018-11-21 15:30:00");
+//! ```
+#![warn(missing_docs)]
+
+extern crate chrono;
+extern crate chrono_tz;
+extern crate regex;
+
+mod converter;
+mod delimited;
+mod error;
+mod field;
+mod format;
+mod json;
+mod logfmt;
+mod registry;
+
+pub use converter::{
+    AmbiguityPolicy, BootTime, Converter, ConverterBuilder, InputMode, OutputFormat, TimeSpan,
+    TimedLog, DEFAULT_KEYS,
+};
+pub use delimited::{Column, Delimiter};
+pub use error::{Error, ParseError};
+pub use format::Format;
+pub use registry::{Preset, DEFAULT_FORMATS, DEFAULT_PRESETS, PRESETS};
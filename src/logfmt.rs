@@ -0,0 +1,94 @@
+// A scanner that finds the values of keys in a logfmt line. Eg. ts=... level=info msg="..."
+// Like the JSON scanner, it only finds where values are so that they can be rewritten in place.
+
+use field::Field;
+
+// Finds the values of the given keys in the line.
+// Words without a value and unterminated quoted values are skipped.
+pub fn find_values(line: &str, keys: &[String]) -> Vec<Field> {
+    let bytes = line.as_bytes();
+    let mut fields = Vec::new();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        if bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+            continue;
+        }
+
+        let key_start = pos;
+        while pos < bytes.len() && bytes[pos] != b'=' && !bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        let key = &line[key_start..pos];
+        if pos == bytes.len() || bytes[pos] != b'=' {
+            continue;
+        }
+        pos += 1;
+
+        let quoted = pos < bytes.len() && bytes[pos] == b'"';
+        let (start, end) = if quoted {
+            pos += 1;
+            let start = pos;
+            while pos < bytes.len() && bytes[pos] != b'"' {
+                pos += if bytes[pos] == b'\\' { 2 } else { 1 };
+            }
+            if pos >= bytes.len() {
+                break;
+            }
+            pos += 1;
+            (start, pos - 1)
+        } else {
+            let start = pos;
+            while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            (start, pos)
+        };
+
+        if keys.iter().any(|k| k == key) {
+            fields.push(Field {
+                start,
+                end,
+                quoted,
+                string: true,
+                spread: false,
+            });
+        }
+    }
+
+    fields
+}
+
+#[cfg(test)]
+mod logfmt_tests {
+    use super::find_values;
+
+    fn values<'a>(line: &'a str, keys: &[&str]) -> Vec<(&'a str, bool)> {
+        let keys: Vec<String> = keys.iter().map(|key| String::from(*key)).collect();
+        find_values(line, &keys)
+            .iter()
+            .map(|field| (&line[field.start..field.end], field.quoted))
+            .collect()
+    }
+
+    #[test]
+    fn test_find_values() {
+        let line = r#"ts=2024-01-02T03:04:05Z level=info msg="at 2024-01-02 03:04:05 \"ts=1\"" debug time="2024-01-02 03:04:05" t= ts"#;
+
+        assert_eq!(
+            values(line, &["ts", "time"]),
+            vec![
+                ("2024-01-02T03:04:05Z", false),
+                ("2024-01-02 03:04:05", true)
+            ]
+        );
+        assert_eq!(
+            values(line, &["msg", "t"]),
+            vec![(r#"at 2024-01-02 03:04:05 \"ts=1\""#, true), ("", false)]
+        );
+        assert_eq!(values(line, &["debug", "level=info"]), vec![]);
+        assert_eq!(values(r#"ts="2024-01-02 unterminated"#, &["ts"]), vec![]);
+        assert_eq!(values("", &["ts"]), vec![]);
+    }
+}
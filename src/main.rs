@@ -4,88 +4,319 @@ extern crate atty;
 extern crate chrono;
 extern crate chrono_tz;
 extern crate colored;
-extern crate regex;
+extern crate notify;
+extern crate tztail;
 mod args;
-mod converter;
-mod format;
+mod merge;
 mod output_formatter;
+mod printer;
 mod reader;
+mod reporter;
+mod tail;
+mod watcher;
+mod window;
 
 use args::Args;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use clap::{App, AppSettings, Arg};
-use converter::Converter;
+use merge::Merge;
+use printer::{Labels, Printer};
 use reader::*;
-use std::error::Error;
-use std::io;
-use std::io::Write;
+use reporter::ParseErrorReporter;
+use std::fs;
+use std::io::{self, BufRead, BufReader};
 use std::process;
+use tail::Tail;
+use tztail::{BootTime, Converter, Error, InputMode, TimedLog, PRESETS};
+use window::{offset_of_since, parse_time, TimeWindow};
 
-fn run(args: Args) -> Result<bool, String> {
+// The number of lines at the start of a file searched for an anchor line before it is read
+const ANCHOR_LINES: usize = 1000;
+
+fn run(args: Args) -> Result<bool, Error> {
     let Args {
-        filename,
+        filenames,
         custom_format: fmt,
         timezone: tz,
-        should_follow: follow,
+        source_timezones,
+        ambiguity,
+        output_format,
+        follow,
+        retry,
+        start,
+        since,
+        until,
+        merge,
+        prefix,
         color_choice,
+        on_parse_error,
+        auto_lock,
+        verbose,
+        epochs,
+        presets,
+        uptime,
+        boot_time,
+        input_mode,
     } = args;
 
-    let c = Converter::new(tz, fmt)?;
     let stdin = io::stdin();
     let stdout = io::stdout();
-    let mut writer = stdout.lock();
 
-    let reader = match filename {
-        Some("-") => InputReader::new(Input::Stdin(&stdin)),
-        Some(name) => InputReader::new(Input::File(&name)),
-        None => InputReader::new(Input::Stdin(&stdin)),
+    let inputs: Vec<Input> = if filenames.is_empty() {
+        vec![Input::Stdin(&stdin)]
+    } else {
+        filenames
+            .iter()
+            .map(|name| match *name {
+                "-" => Input::Stdin(&stdin),
+                name => Input::File(name),
+            })
+            .collect()
     };
 
-    let mut reader = match reader {
-        Ok(r) => r,
-        Err(err) => return handle_err(err),
+    // Ambiguous times are only marked in text, where the mark does not break the line
+    let marked = input_mode == InputMode::Text;
+    let mut builder = Converter::builder();
+    builder
+        .ambiguity(ambiguity)
+        .output_format(output_format)
+        .input_mode(input_mode);
+    if let Some(tz) = tz {
+        builder.timezone(tz);
+    }
+    if let Some(fmt) = fmt {
+        builder.format(fmt);
+    }
+    for name in presets {
+        builder.preset(name);
+    }
+    for context in epochs {
+        builder.epoch(context);
+    }
+    if let Some(lines) = auto_lock {
+        builder.auto_lock(lines);
+    }
+    // The boot time is learnt from anchor lines in the log when asked for.
+    // Otherwise it is the boot time of this machine, which is right for its own dmesg.
+    let boot_time = match boot_time {
+        Some("anchor") => Some(BootTime::Anchored(None)),
+        Some(spec) => {
+            let timezone = tz.and_then(|tz| tz.parse::<Tz>().ok());
+            Some(BootTime::Fixed(
+                parse_time(spec, timezone, Utc::now()).map_err(Error::Parse)?,
+            ))
+        }
+        None if uptime => {
+            let boot_time = system_boot_time().ok_or_else(|| {
+                Error::UnknownBootTime(String::from(
+                    "there is no btime in /proc/stat, give it with --boot-time",
+                ))
+            })?;
+            // Logs copied from another machine would be converted wrongly without a word
+            eprintln!(
+                "tztail: using the boot time of this machine, {}. Give it with --boot-time for the logs of another machine",
+                boot_time.to_rfc3339()
+            );
+            Some(BootTime::Fixed(boot_time))
+        }
+        None => None,
     };
+    if let Some(boot_time) = boot_time {
+        builder.uptime(boot_time);
+    }
 
-    let mut has_next = true;
-    let mut buf = String::new();
+    let mut converters = Vec::with_capacity(inputs.len());
+    for (i, input) in inputs.iter().enumerate() {
+        let mut builder = builder.clone();
+        if let Some(source_tz) = source_timezones.get(i).or_else(|| source_timezones.first()) {
+            builder.source_timezone(source_tz);
+        }
+        // The year of datetimes without one is inferred from when a file was last written.
+        // Followed files and STDIN are still being written, so the current time is used.
+        if let (Follow::Never, &Input::File(path)) = (follow, input) {
+            if let Ok(modified) = fs::metadata(path).and_then(|metadata| metadata.modified()) {
+                builder.reference_time(DateTime::<Utc>::from(modified));
+            }
+        }
+        let mut converter = builder.build()?;
+        // The header of a file is read up front, as lines can be read before it with --merge or --since
+        if let (true, &Input::File(path)) = (converter.needs_header(), input) {
+            // Files that cannot be opened yet get their header when they are read, with --retry
+            if let Ok(Some(header)) = first_line(path) {
+                converter.read_header(&header)?;
+            }
+        }
+        // Uptimes before the first anchor line of a file are converted with its boot time too
+        if let (Some(BootTime::Anchored(_)), &Input::File(path)) = (boot_time, input) {
+            if let Err(err) = first_anchor(path, &mut converter) {
+                eprintln!(
+                    "tztail: '{}' could not be searched for an anchor line: {}",
+                    path, err
+                );
+            }
+        }
+        converters.push(converter);
+    }
+
+    let window = TimeWindow::new(since, until, converters[0].timezone())?;
+
+    let mut readers = Vec::with_capacity(inputs.len());
+    for (input, converter) in inputs.into_iter().zip(&converters) {
+        // Regular files can be searched for where --since begins, instead of being read from the start
+        let start = match (&input, window.since, start) {
+            (&Input::File(path), Some(since), Start::Beginning) if is_regular_file(path) => {
+                Start::Offset(offset_of_since(path, converter, since)?)
+            }
+            _ => start,
+        };
 
-    let formatter = color_choice.build_formatter();
+        readers.push(InputReader::new(input, follow, retry, start)?);
+    }
 
-    match write!(
-        writer,
-        "{}",
-        formatter.format(c.convert(reader.first_line()))
-    ) {
-        Ok(_) => (),
-        Err(err) => return handle_err(err),
+    // Like tail, headers are shown only when there are many inputs.
+    // They are left out when merging, as the input changes on almost every line.
+    let labels = if prefix {
+        Labels::Prefix
+    } else if readers.len() > 1 && !merge {
+        Labels::Headers
+    } else {
+        Labels::Nothing
     };
+    let mut formatter = color_choice.build_formatter();
+    if !marked {
+        formatter = formatter.unmarked();
+    }
+    let mut printer = Printer::new(stdout.lock(), formatter, readers.len(), labels);
+    let mut buf = String::new();
+    // Whether the last line of each input was shown, which lines without a timestamp go along with
+    let mut shown = vec![window.since.is_none(); readers.len()];
+    let mut parse_errors = ParseErrorReporter::new(on_parse_error);
 
-    while follow || has_next {
-        match reader.read_line(&mut buf) {
-            Ok(bytes) if bytes > 0 => {
-                match write!(writer, "{}", formatter.format(c.convert(&buf))) {
-                    Ok(_) => (),
-                    Err(err) => return handle_err(err),
-                }
+    if merge {
+        let mut merge = Merge::new(readers, &mut converters, verbose)?;
 
-                buf.clear();
-                has_next = true;
+        while let Some((input, record)) = merge.next_record(&mut converters)? {
+            if !window.admits(record.instant, &mut shown[input]) {
+                continue;
             }
-            Ok(_) => {
-                has_next = false;
+
+            for mut line in record.lines {
+                let log = line.take_log();
+                parse_errors.check(&log)?;
+                printer.print(input, merge.name(input), log)?;
+            }
+        }
+    } else {
+        let mut tail = Tail::new(readers);
+
+        while let Some(input) = tail.next_line(&mut buf)? {
+            learn(&mut converters[input], &buf, tail.name(input), verbose)?;
+            let log = convert(&mut converters[input], &buf, tail.name(input), verbose);
+            if !window.admits(log.instant(), &mut shown[input]) {
+                continue;
+            }
+
+            parse_errors.check(&log)?;
+            printer.print(input, tail.name(input), log)?;
+        }
+    }
+
+    parse_errors.finish();
+    Ok(true)
+}
+
+// Learns the format of an input from its line when auto-lock is enabled.
+// The locked format is reported on STDERR when verbose.
+// The first line is the header when columns are named and it is not read yet, like on STDIN.
+fn learn(converter: &mut Converter, line: &str, name: &str, verbose: bool) -> Result<(), Error> {
+    if converter.needs_header() {
+        converter.read_header(line)?;
+    }
+    if let Some(fmt) = converter.learn(line) {
+        if verbose {
+            eprintln!("tztail: {}: locked onto format '{}'", name, fmt);
+        }
+    }
+    Ok(())
+}
+
+// Converts a line, learning the boot time from it when it is an anchor line.
+// An anchor line is converted again, so that its uptime is converted with the boot time it has.
+// The boot time is reported on STDERR when verbose.
+fn convert<'a>(
+    converter: &mut Converter,
+    line: &'a str,
+    name: &str,
+    verbose: bool,
+) -> TimedLog<'a> {
+    let log = converter.convert(line);
+    match converter.read_anchor(&log) {
+        Some(boot_time) => {
+            if verbose {
+                eprintln!("tztail: {}: booted at {}", name, boot_time.to_rfc3339());
             }
-            Err(err) => return handle_err(err),
+            converter.convert(line)
         }
+        None => log,
     }
+}
 
-    return Ok(true);
+// Reads the first line of a file, if there is one
+fn first_line(path: &str) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    match BufReader::new(fs::File::open(path)?).read_line(&mut line)? {
+        0 => Ok(None),
+        _ => Ok(Some(line)),
+    }
 }
 
-fn handle_err(err: std::io::Error) -> Result<bool, String> {
-    eprintln!("{}: {}", "Exited while reading lines", err.description());
-    return Err(format!("Exited while reading lines: {}", err));
+// Prints the presets along with their formats, contexts and an example line
+fn list_presets() {
+    for preset in PRESETS {
+        println!("{}", preset.name);
+        println!("  format:  {}", preset.format);
+        println!("  context: {}", preset.context);
+        println!("  example: {}", preset.example);
+    }
+}
+
+// Reads a file up to its first anchor line, to learn the boot time.
+// Only its first ANCHOR_LINES lines are read, so that large files without one do not hold up the output.
+// Files that cannot be opened are skipped, as opening them is reported or retried when they are read.
+fn first_anchor(path: &str, converter: &mut Converter) -> io::Result<()> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Ok(()),
+    };
+    let lines = BufReader::new(file).lines();
+    for line in lines.take(ANCHOR_LINES) {
+        let line = line?;
+        let log = converter.convert(&line);
+        if converter.read_anchor(&log).is_some() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// The time this machine booted at, from btime in /proc/stat on Linux
+fn system_boot_time() -> Option<DateTime<Utc>> {
+    let stat = fs::read_to_string("/proc/stat").ok()?;
+    let btime = stat.lines().find_map(|line| line.strip_prefix("btime "))?;
+    Some(DateTime::<Utc>::from(
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs(btime.trim().parse().ok()?),
+    ))
+}
+
+fn is_regular_file(path: &str) -> bool {
+    fs::metadata(path)
+        .map(|metadata| metadata.is_file())
+        .unwrap_or(false)
 }
 
 fn main() {
+    let preset_names: Vec<&str> = PRESETS.iter().map(|preset| preset.name).collect();
     let app = App::new(crate_name!())
         .setting(AppSettings::ColorAuto)
         .setting(AppSettings::ColoredHelp)
@@ -93,7 +324,11 @@ fn main() {
         .setting(AppSettings::UnifiedHelpMessage)
         .version(crate_version!())
         .about("tztail (TimeZoneTAIL) allows you to view logs in the timezone you want")
-        .arg(Arg::with_name("FILE").help("File to tail. STDIN by default"))
+        .arg(
+            Arg::with_name("FILE")
+                .multiple(true)
+                .help("Files to tail. STDIN by default"),
+        )
         .arg(
             Arg::with_name("timezone")
                 .long("timezone")
@@ -101,7 +336,23 @@ fn main() {
                 .value_name("TIMEZONE")
                 .required(false)
                 .takes_value(true)
-                .help("Sets the timezone in which output should be printed. (Default: local timezone)"),
+                .help("Sets the timezone in which output should be printed. It is an error if the timezone is not valid. (Default: local timezone)"),
+        ).arg(
+            Arg::with_name("source-timezone")
+                .long("source-timezone")
+                .value_name("TIMEZONE")
+                .required(false)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Timezone of datetimes in the log that have no offset. Repeat it to give one for each file in order. (Default: UTC)"),
+        ).arg(
+            Arg::with_name("ambiguous")
+                .long("ambiguous")
+                .value_name("POLICY")
+                .possible_values(&["earliest", "latest", "error"])
+                .required(false)
+                .help("How to resolve local times made ambiguous or non-existent by DST in the source timezone. Times resolved this way are marked with a trailing '*' in text lines. (Default: earliest)"),
         ).arg(
             Arg::with_name("follow")
                 .long("follow")
@@ -109,7 +360,44 @@ fn main() {
                 .value_name("FOLLOW")
                 .required(false)
                 .takes_value(false)
-                .help("Follow the file indefinitely as changes are added. (Default: Off)"),
+                .help("Follow the file indefinitely as changes are added. Known limitation: STDIN cannot be followed along with other files, as reading it holds them up. (Default: Off)"),
+        ).arg(
+            Arg::with_name("lines")
+                .long("lines")
+                .short("n")
+                .value_name("NUM")
+                .required(false)
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("Output only the last NUM lines, or use +NUM to start from line NUM. (Default: all lines)"),
+        ).arg(
+            Arg::with_name("since")
+                .long("since")
+                .value_name("TIME")
+                .required(false)
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("Output only lines at or after TIME. Either relative like -15m, -2h, -1d or a datetime like '2018-11-21 14:00' read in the target timezone, unless followed by a timezone name. (Default: Off)"),
+        ).arg(
+            Arg::with_name("until")
+                .long("until")
+                .value_name("TIME")
+                .required(false)
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("Output only lines at or before TIME, in the same forms as --since. (Default: Off)"),
+        ).arg(
+            Arg::with_name("follow-name")
+                .short("F")
+                .required(false)
+                .takes_value(false)
+                .help("Follow the file by name, reopening it when it is rotated or truncated. Implies --retry. (Default: Off)"),
+        ).arg(
+            Arg::with_name("retry")
+                .long("retry")
+                .required(false)
+                .takes_value(false)
+                .help("Keep trying to open the file until it becomes accessible when following. (Default: Off)"),
         ).arg(
             Arg::with_name("format")
                 .long("format")
@@ -117,6 +405,125 @@ fn main() {
                 .required(false)
                 .takes_value(true)
                 .help("Custom format for parsing dates. (Default: autodetected patterns)")
+        ).arg(
+            Arg::with_name("preset")
+                .long("preset")
+                .value_name("PRESET")
+                .possible_values(&preset_names)
+                .required(false)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Convert only the datetimes of the logs of a program, where the program puts them. Like --format, it replaces the autodetected patterns and can be repeated. See --list-presets. (Default: Off)")
+        ).arg(
+            Arg::with_name("list-presets")
+                .long("list-presets")
+                .required(false)
+                .takes_value(false)
+                .help("List the presets with their formats, the context they are found in and an example line, then exit")
+        ).arg(
+            Arg::with_name("epoch")
+                .long("epoch")
+                .value_name("CONTEXT")
+                .required(false)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Also convert epoch timestamps of 10, 13, 16 or 19 digits (seconds, millis, micros or nanos) that follow CONTEXT. Eg. '\"ts\":' or 'time='. Repeat it for more contexts. They are printed unchanged unless --output-format is given, and quoted if they are no longer a number. (Default: Off)")
+        ).arg(
+            Arg::with_name("json")
+                .long("json")
+                .required(false)
+                .takes_value(false)
+                .help("Read each line as a JSON object and convert only the values of --key. Number values are taken as epochs and are quoted when converted. Lines that are not JSON are printed as they are. (Default: Off)")
+        ).arg(
+            Arg::with_name("logfmt")
+                .long("logfmt")
+                .required(false)
+                .takes_value(false)
+                .conflicts_with("json")
+                .help("Read each line as logfmt, like ts=... level=info msg=\"...\", and convert only the values of --key. The rest of the line is printed as it is. (Default: Off)")
+        ).arg(
+            Arg::with_name("column")
+                .long("column")
+                .value_name("COLUMN")
+                .required(false)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .conflicts_with_all(&["json", "logfmt"])
+                .help("Read each line as delimited columns and convert only COLUMN. Either a number counting from 1, a range like 1-2 for a datetime spread over columns, or a name in the header, which is the first line. Fields in double quotes can have the delimiter in them. Repeat it for more columns. (Default: Off)")
+        ).arg(
+            Arg::with_name("delimiter")
+                .long("delimiter")
+                .value_name("DELIMITER")
+                .required(false)
+                .takes_value(true)
+                .requires("column")
+                .help("Separates the columns of --column. A single character, tab or whitespace for any run of spaces and tabs. (Default: ,)")
+        ).arg(
+            Arg::with_name("key")
+                .long("key")
+                .value_name("KEY")
+                .required(false)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Key holding a datetime with --json or --logfmt. Keys of nested JSON objects are joined with a dot, like meta.time. Repeat it for more keys. (Default: time, ts, timestamp, @timestamp)")
+        ).arg(
+            Arg::with_name("uptime")
+                .long("uptime")
+                .required(false)
+                .takes_value(false)
+                .help("Convert the seconds since boot at the start of kernel log lines, like [ 12345.678901] of dmesg, to the time they were logged. The boot time is btime in /proc/stat, with a warning on STDERR, unless given with --boot-time. (Default: Off)")
+        ).arg(
+            Arg::with_name("boot-time")
+                .long("boot-time")
+                .value_name("TIME")
+                .required(false)
+                .takes_value(true)
+                .help("The time the machine booted at, for --uptime. Implies --uptime. Either relative like -2h or a datetime like '2018-11-21 14:00', in the same forms as --since. Or anchor to learn it from anchor lines, which are kernel log lines setting the system clock like '[    1.234567] rtc_cmos 00:00: setting system clock to 2018-11-04T12:13:49 UTC'. Uptimes before the first one use it too when it is in the first 1000 lines of a file. (Default: btime in /proc/stat)")
+        ).arg(
+            Arg::with_name("output-format")
+                .long("output-format")
+                .value_name("OUTPUT_FORMAT")
+                .required(false)
+                .takes_value(true)
+                .help("Format for printing converted dates. One of the presets rfc3339, iso-utc, same or a custom strftime format. (Default: same as parsed format)")
+        ).arg(
+            Arg::with_name("merge")
+                .long("merge")
+                .required(false)
+                .takes_value(false)
+                .conflicts_with_all(&["follow", "follow-name"])
+                .help("Merge the files into a single timeline ordered by their timestamps. Lines without a timestamp stay with the line before them. (Default: Off)")
+        ).arg(
+            Arg::with_name("prefix")
+                .long("prefix")
+                .required(false)
+                .takes_value(false)
+                .help("Prefix every line with the name of the file it came from instead of printing headers. Each file gets its own color when output is colored. (Default: Off)")
+        ).arg(
+            Arg::with_name("on-parse-error")
+                .long("on-parse-error")
+                .value_name("POLICY")
+                .possible_values(&["ignore", "warn", "fail"])
+                .required(false)
+                .help("What to do with datetimes that cannot be parsed. warn reports the first one and how many there were at the end. fail stops at the line with the error. (Default: warn)")
+        ).arg(
+            Arg::with_name("auto-lock")
+                .long("auto-lock")
+                .value_name("LINES")
+                .required(false)
+                .takes_value(true)
+                .help("Find the format used in most of the first LINES lines of each file and only look for it after that, leaving other datetimes in the line alone. Lines where it is not found are still searched for all formats. (Default: Off)")
+        ).arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .required(false)
+                .takes_value(false)
+                .help("Report details like the format locked onto on STDERR. (Default: Off)")
         ).arg(
             Arg::with_name("color")
                 .long("color")
@@ -126,11 +533,17 @@ fn main() {
                 .help("Controls when to use color")
         );
 
-    let result = Args::parse(&app.get_matches()).and_then(run);
+    let matches = app.get_matches();
+    if matches.is_present("list-presets") {
+        list_presets();
+        process::exit(0);
+    }
+
+    let result = Args::parse(&matches).and_then(|args| run(args).map_err(|err| err.to_string()));
 
     match result {
         Err(error) => {
-            eprintln!("{}: {}", "Exited non-successfully", error);
+            eprintln!("Exited non-successfully: {}", error);
             process::exit(1);
         }
         Ok(false) => process::exit(1),
@@ -0,0 +1,185 @@
+use chrono::{DateTime, Utc};
+use reader::InputReader;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::mem;
+use tztail::{Converter, Error, ParseError, TimeSpan, TimedLog};
+use {convert, learn};
+
+// Record is a line with a timestamp, along with the lines following it that have none,
+// like the lines of a stack trace. They are kept together when merging.
+// Lines at the start of an input before any timestamp form a record without an instant.
+pub struct Record {
+    pub instant: Option<DateTime<Utc>>,
+    pub lines: Vec<Line>,
+}
+
+// Line is a line of an input along with its timestamps, converted once when it is read
+pub struct Line {
+    text: String,
+    spans: Vec<TimeSpan>,
+    errors: Vec<ParseError>,
+}
+
+impl Line {
+    // Learns from the line and converts it, like lines that are not merged
+    fn read(
+        text: String,
+        converter: &mut Converter,
+        name: &str,
+        verbose: bool,
+    ) -> Result<Line, Error> {
+        learn(converter, &text, name, verbose)?;
+        let TimedLog { spans, errors, .. } = convert(converter, &text, name, verbose);
+        Ok(Line {
+            text,
+            spans,
+            errors,
+        })
+    }
+
+    fn instant(&self) -> Option<DateTime<Utc>> {
+        self.spans.first().map(|span| span.instant)
+    }
+
+    // Takes the line as it was converted, leaving it without timestamps
+    pub fn take_log(&mut self) -> TimedLog<'_> {
+        TimedLog {
+            log: &self.text,
+            converted: !self.spans.is_empty(),
+            spans: mem::take(&mut self.spans),
+            errors: mem::take(&mut self.errors),
+        }
+    }
+}
+
+// Merge interleaves the records of several inputs into a single timeline ordered by instant.
+// Each input is expected to be in chronological order already, so this is a k-way merge.
+// On equal instants, records of the input listed first come first.
+//
+// heads holds the instant of the next record of every input that is not exhausted.
+// Lines are learnt from and converted as they are read, as records are read ahead of being merged.
+pub struct Merge<'a> {
+    sources: Vec<MergeSource<'a>>,
+    heads: BinaryHeap<Reverse<(Option<DateTime<Utc>>, usize)>>,
+    verbose: bool,
+}
+
+impl<'a> Merge<'a> {
+    // Instantiate Merge from the readers of all inputs.
+    // converters[i] is used to find the timestamps of the i-th input.
+    pub fn new(
+        readers: Vec<InputReader<'a>>,
+        converters: &mut [Converter],
+        verbose: bool,
+    ) -> Result<Merge<'a>, Error> {
+        let mut merge = Merge {
+            sources: Vec::with_capacity(readers.len()),
+            heads: BinaryHeap::new(),
+            verbose,
+        };
+        for (reader, converter) in readers.into_iter().zip(converters.iter_mut()) {
+            merge
+                .sources
+                .push(MergeSource::new(reader, converter, verbose)?);
+        }
+
+        for (i, converter) in converters.iter_mut().enumerate() {
+            merge.advance(i, converter)?;
+        }
+
+        Ok(merge)
+    }
+
+    // Returns the earliest record among all inputs, along with the index of its input.
+    // Returns None once all inputs are exhausted.
+    pub fn next_record(
+        &mut self,
+        converters: &mut [Converter],
+    ) -> Result<Option<(usize, Record)>, Error> {
+        let i = match self.heads.pop() {
+            Some(Reverse((_, i))) => i,
+            None => return Ok(None),
+        };
+
+        let record = self.sources[i].record.take();
+        self.advance(i, &mut converters[i])?;
+
+        Ok(record.map(|record| (i, record)))
+    }
+
+    // Reads the next record of the i-th input and queues it up
+    fn advance(&mut self, i: usize, converter: &mut Converter) -> Result<(), Error> {
+        let source = &mut self.sources[i];
+        source.record = source.read_record(converter, self.verbose)?;
+        if let Some(ref record) = source.record {
+            self.heads.push(Reverse((record.instant, i)));
+        }
+        Ok(())
+    }
+
+    // Name of the i-th input
+    pub fn name(&self, i: usize) -> &str {
+        self.sources[i].reader.name()
+    }
+}
+
+// MergeSource reads records from a single input.
+// lookahead is the line that starts the next record, as it is read while looking for the end of a record.
+struct MergeSource<'a> {
+    reader: InputReader<'a>,
+    record: Option<Record>,
+    lookahead: Option<Line>,
+}
+
+impl<'a> MergeSource<'a> {
+    fn new(
+        reader: InputReader<'a>,
+        converter: &mut Converter,
+        verbose: bool,
+    ) -> Result<MergeSource<'a>, Error> {
+        let first_line = String::from(reader.first_line());
+        let lookahead = if first_line.is_empty() {
+            None
+        } else {
+            Some(Line::read(first_line, converter, reader.name(), verbose)?)
+        };
+
+        Ok(MergeSource {
+            reader,
+            record: None,
+            lookahead,
+        })
+    }
+
+    // Reads lines till the start of the next record. Returns None once the input is exhausted.
+    fn read_record(
+        &mut self,
+        converter: &mut Converter,
+        verbose: bool,
+    ) -> Result<Option<Record>, Error> {
+        let mut record = match self.lookahead.take() {
+            Some(line) => Record {
+                instant: line.instant(),
+                lines: vec![line],
+            },
+            None => return Ok(None),
+        };
+
+        loop {
+            let mut text = String::new();
+            if self.reader.read_line(&mut text)? == 0 {
+                break;
+            }
+
+            let line = Line::read(text, converter, self.reader.name(), verbose)?;
+            if line.instant().is_some() {
+                self.lookahead = Some(line);
+                break;
+            }
+            record.lines.push(line);
+        }
+
+        Ok(Some(record))
+    }
+}
@@ -1,34 +1,158 @@
 use colored::*;
-use converter::TimedLog;
+use std::fmt::Write;
+use tztail::TimedLog;
+
+// Colors cycled through for tagging inputs.
+// Red and yellow are left out as they are used for the converted times.
+const TAG_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Magenta,
+    Color::Blue,
+    Color::BrightCyan,
+    Color::BrightGreen,
+];
 
 // OutputFormatter can either format the target time as a colored
 // string or a plain string based on a flag
 pub struct OutputFormatter {
     colored: bool,
+    marked: bool,
 }
 
 impl OutputFormatter {
     pub fn plain() -> OutputFormatter {
-        OutputFormatter { colored: false }
+        OutputFormatter {
+            colored: false,
+            marked: true,
+        }
     }
 
     pub fn colored() -> OutputFormatter {
-        OutputFormatter { colored: true }
+        OutputFormatter {
+            colored: true,
+            marked: true,
+        }
+    }
+
+    // unmarked leaves out the '*' after ambiguous times, which would break the values of structured lines.
+    // They are still colored differently.
+    pub fn unmarked(self) -> OutputFormatter {
+        OutputFormatter {
+            marked: false,
+            ..self
+        }
     }
 
-    // format does a string replace of the original_time in log
-    // TODO: Is string replace slow? Is there a better way?
-    pub fn format(&self, t: TimedLog) -> String {
-        if t.converted {
-            let target_time = if self.colored {
-                format!("{}", t.target_time.unwrap().red())
+    // tag renders the name of the input a line came from, to be put before the line.
+    // When colored, every input gets its own color so that interleaved lines can be told apart.
+    pub fn tag(&self, name: &str, input: usize, out: &mut String) {
+        out.clear();
+        if self.colored {
+            let color = TAG_COLORS[input % TAG_COLORS.len()];
+            let _ = write!(out, "{}: ", name.color(color));
+        } else {
+            let _ = write!(out, "{}: ", name);
+        }
+    }
+
+    // format rewrites the byte range of every span in log with its target time.
+    // The result is written into out, which is cleared first so that it can be reused across lines.
+    pub fn format(&self, t: TimedLog, out: &mut String) {
+        out.clear();
+
+        let mut last = 0;
+        for span in t.spans {
+            out.push_str(&t.log[last..span.start]);
+
+            // Times picked by the ambiguity policy are marked so that they can be told apart
+            let mark = if span.ambiguous && self.marked {
+                "*"
             } else {
-                t.target_time.unwrap()
+                ""
             };
+            match (self.colored, span.ambiguous) {
+                (true, true) => {
+                    let _ = write!(out, "{}", format!("{}{}", span.target_time, mark).yellow());
+                }
+                (true, false) => {
+                    let _ = write!(out, "{}", span.target_time.red());
+                }
+                (false, _) => {
+                    out.push_str(&span.target_time);
+                    out.push_str(mark);
+                }
+            }
 
-            return t.log.replace(&t.original_time.unwrap(), &target_time);
+            last = span.end;
         }
+        out.push_str(&t.log[last..]);
+    }
+}
+
+#[cfg(test)]
+mod output_formatter_tests {
+    use chrono::{TimeZone, Utc};
+    use tztail::{TimeSpan, TimedLog};
+
+    #[test]
+    fn test_format_rewrites_only_spans() {
+        let log = "2018-11-03 22:39:33 retried job queued at 2018-11-03 22:39:33";
+        let t = TimedLog {
+            log,
+            converted: true,
+            errors: vec![],
+            spans: vec![TimeSpan {
+                start: 0,
+                end: 19,
+                original_time: String::from("2018-11-03 22:39:33"),
+                instant: Utc.ymd(2018, 11, 3).and_hms(22, 39, 33),
+                target_time: String::from("2018-11-04 04:09:33"),
+                ambiguous: false,
+            }],
+        };
+
+        let mut out = String::from("left over from previous line");
+        super::OutputFormatter::plain().format(t, &mut out);
+        assert_eq!(
+            out,
+            "2018-11-04 04:09:33 retried job queued at 2018-11-03 22:39:33"
+        );
+    }
+
+    #[test]
+    fn test_format_marks_ambiguous_spans() {
+        let t = || TimedLog {
+            log: "a 01:30 b 02:30 c",
+            converted: true,
+            errors: vec![],
+            spans: vec![
+                TimeSpan {
+                    start: 2,
+                    end: 7,
+                    original_time: String::from("01:30"),
+                    instant: Utc.ymd(2018, 11, 3).and_hms(22, 39, 33),
+                    target_time: String::from("06:30"),
+                    ambiguous: true,
+                },
+                TimeSpan {
+                    start: 10,
+                    end: 15,
+                    original_time: String::from("02:30"),
+                    instant: Utc.ymd(2018, 11, 3).and_hms(22, 39, 33),
+                    target_time: String::from("07:30"),
+                    ambiguous: false,
+                },
+            ],
+        };
+
+        let mut out = String::new();
+        super::OutputFormatter::plain().format(t(), &mut out);
+        assert_eq!(out, "a 06:30* b 07:30 c");
 
-        String::from(t.log)
+        super::OutputFormatter::plain()
+            .unmarked()
+            .format(t(), &mut out);
+        assert_eq!(out, "a 06:30 b 07:30 c");
     }
 }
@@ -0,0 +1,64 @@
+use output_formatter::OutputFormatter;
+use std::io::{self, Write};
+use tztail::TimedLog;
+
+// Printer writes converted lines to the output, labelled with the input they came from.
+pub struct Printer<W: Write> {
+    writer: W,
+    formatter: OutputFormatter,
+    multiple: bool,
+    labels: Labels,
+    last_input: Option<usize>,
+    out: String,
+}
+
+// Labels decides how lines of different inputs are told apart
+// Nothing does not label lines at all
+// Headers prints the name of the input before its lines whenever the input changes, like tail does
+// Prefix prefixes every line with the name of its input
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Labels {
+    Nothing,
+    Headers,
+    Prefix,
+}
+
+impl<W: Write> Printer<W> {
+    // Instantiate Printer for `inputs` number of inputs
+    pub fn new(writer: W, formatter: OutputFormatter, inputs: usize, labels: Labels) -> Printer<W> {
+        Printer {
+            writer,
+            formatter,
+            multiple: inputs > 1,
+            labels,
+            last_input: None,
+            out: String::new(),
+        }
+    }
+
+    // Writes a converted line read from the input with the given index and name
+    pub fn print(&mut self, input: usize, name: &str, log: TimedLog) -> io::Result<()> {
+        match self.labels {
+            Labels::Prefix => {
+                self.formatter.tag(name, input, &mut self.out);
+                self.writer.write_all(self.out.as_bytes())?;
+            }
+            Labels::Headers if self.last_input != Some(input) => {
+                let separator = if self.last_input.is_some() { "\n" } else { "" };
+                writeln!(self.writer, "{}==> {} <==", separator, name)?;
+            }
+            _ => (),
+        }
+        self.last_input = Some(input);
+
+        // Lines from different inputs must not run into each other
+        let missing_newline = self.multiple && !log.log.ends_with('\n');
+
+        self.formatter.format(log, &mut self.out);
+        self.writer.write_all(self.out.as_bytes())?;
+        if missing_newline {
+            self.writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
@@ -1,57 +1,379 @@
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Stdin};
+use std::collections::VecDeque;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom, Stdin};
+use watcher::FileWatcher;
+
+// Size of the chunks read when seeking backwards from the end of a file
+const CHUNK_SIZE: usize = 64 * 1024;
 
 // Abstraction to read from source
-// first_line is read first.
-// This can be used to further optimization if needed like autodetecting the format before starting conversion.
+// first_line is read first, after skipping to where reading starts.
+// name is how the source is shown in headers and prefixes.
+// followed is set when following a file, to detect it appearing, being rotated or truncated.
+// pending is the reopened file to switch to once the current one is read till EOF.
 pub struct InputReader<'a> {
     reader: Box<dyn BufRead + 'a>,
     pub first_line: String,
+    name: String,
+    followed: Option<FollowedFile>,
+    pending: Option<File>,
 }
 
-// Input can either be a single file or STDIN
+// Input can either be a file or STDIN
 pub enum Input<'a> {
     Stdin(&'a Stdin),
     File(&'a str),
 }
 
+// Follow decides what happens when the reader reaches EOF
+// Never stops reading.
+// Descriptor waits for more data to be written to the opened file.
+// Name waits for more data to be written to the file at the path, reopening
+// it when it is renamed, deleted and recreated, or truncated. This is how log rotation is followed.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Follow {
+    Never,
+    Descriptor,
+    Name,
+}
+
+// Start decides where reading an input begins
+// Beginning reads from the first line.
+// LastLines(n) reads only the last n lines, like `tail -n n`.
+// FromLine(n) skips to the n-th line, counting from 1, like `tail -n +n`.
+// Offset(n) starts at the n-th byte of a file, which is expected to be the start of a line.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Start {
+    Beginning,
+    LastLines(u64),
+    FromLine(u64),
+    Offset(u64),
+}
+
+impl Start {
+    // Instantiate Start based on cli option chosen. Eg. "10", "-10" or "+10"
+    pub fn new(choice: Option<&str>) -> Result<Start, String> {
+        let choice = match choice {
+            Some(choice) => choice,
+            None => return Ok(Start::Beginning),
+        };
+
+        let parse = |n: &str| {
+            n.parse::<u64>()
+                .map_err(|err| format!("Invalid number of lines '{}': {}", choice, err))
+        };
+
+        if let Some(n) = choice.strip_prefix('+') {
+            parse(n).map(Start::FromLine)
+        } else {
+            parse(choice.trim_start_matches('-')).map(Start::LastLines)
+        }
+    }
+}
+
 impl<'a> InputReader<'a> {
     // Instantiate InputReader based on the type of Input
-    pub fn new(input: Input) -> io::Result<InputReader> {
+    // If `retry` is set while following, a file that cannot be opened is waited for instead of failing.
+    pub fn new(
+        input: Input<'a>,
+        follow: Follow,
+        retry: bool,
+        start: Start,
+    ) -> io::Result<InputReader<'a>> {
         match input {
             Input::Stdin(stdin) => {
-                let mut reader = stdin.lock();
+                let reader: Box<dyn BufRead + 'a> = match start {
+                    Start::LastLines(n) => Box::new(last_lines(stdin.lock(), n)?),
+                    _ => Box::new(stdin.lock()),
+                };
 
-                let mut first_line = String::new();
-                reader.read_line(&mut first_line)?;
+                let mut reader = InputReader {
+                    reader,
+                    first_line: String::new(),
+                    name: String::from("standard input"),
+                    followed: None,
+                    pending: None,
+                };
+                reader.start_at(start)?;
 
-                Ok(InputReader {
-                    reader: Box::new(reader),
-                    first_line: first_line,
-                })
+                Ok(reader)
             }
             Input::File(filename) => {
-                let file = File::open(filename)?;
-                let mut reader = BufReader::new(file);
+                let file = match File::open(filename) {
+                    Ok(file) => Some(file),
+                    Err(ref err) if retry && follow != Follow::Never => {
+                        eprintln!(
+                            "tztail: cannot open '{}' for reading: {}; retrying",
+                            filename, err
+                        );
+                        None
+                    }
+                    Err(err) => return Err(err),
+                };
+
+                let followed = match follow {
+                    Follow::Never => None,
+                    _ => Some(FollowedFile::new(filename, file.as_ref(), follow)),
+                };
+
+                let mut reader = InputReader {
+                    reader: Box::new(io::empty()),
+                    first_line: String::new(),
+                    name: String::from(filename),
+                    followed,
+                    pending: None,
+                };
+
+                if let Some(mut file) = file {
+                    reader.reader = match start {
+                        Start::LastLines(n) if file.metadata()?.is_file() => {
+                            let offset = offset_of_last_lines(&mut file, n)?;
+                            if let Some(ref mut followed) = reader.followed {
+                                followed.position = offset;
+                            }
+                            Box::new(BufReader::new(file))
+                        }
+                        // Pipes and other special files cannot seek
+                        Start::LastLines(n) => Box::new(last_lines(BufReader::new(file), n)?),
+                        Start::Offset(offset) => {
+                            file.seek(SeekFrom::Start(offset))?;
+                            if let Some(ref mut followed) = reader.followed {
+                                followed.position = offset;
+                            }
+                            Box::new(BufReader::new(file))
+                        }
+                        _ => Box::new(BufReader::new(file)),
+                    };
+                }
+                reader.start_at(start)?;
+
+                Ok(reader)
+            }
+        }
+    }
 
-                let mut first_line = String::new();
-                reader.read_line(&mut first_line)?;
+    // Skips lines as needed for FromLine and reads the first line
+    fn start_at(&mut self, start: Start) -> io::Result<()> {
+        let mut first_line = String::new();
 
-                Ok(InputReader {
-                    reader: Box::new(reader),
-                    first_line: first_line,
-                })
+        if let Start::FromLine(n) = start {
+            for _ in 1..n {
+                first_line.clear();
+                if self.read_line(&mut first_line)? == 0 {
+                    break;
+                }
+            }
+            first_line.clear();
+        }
+
+        self.read_line(&mut first_line)?;
+        self.first_line = first_line;
+        Ok(())
+    }
+
+    // Returns true if more data can arrive after reaching EOF.
+    // This is never the case for STDIN or when not following.
+    pub fn is_followed(&self) -> bool {
+        self.followed.is_some()
+    }
+
+    // Adds the paths that need to be watched to notice new data on this input
+    pub fn watch(&self, watcher: &mut FileWatcher) {
+        if let Some(ref followed) = self.followed {
+            if followed.follow == Follow::Name || followed.missing {
+                watcher.watch_parent(&followed.path);
+            } else {
+                watcher.watch(&followed.path);
+            }
+        }
+    }
+
+    // Checks the followed file after waiting for changes, and schedules
+    // a switch over to the file at the path if it has appeared, been rotated or truncated.
+    pub fn refresh(&mut self) {
+        if self.pending.is_none() {
+            if let Some(ref mut followed) = self.followed {
+                self.pending = followed.reopen_if_changed();
             }
         }
     }
 
     // Proxy call for read_line of the underling BufRead
+    // Switches over to a reopened file once the current one is exhausted.
     pub fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
-        self.reader.read_line(buf)
+        let bytes = self.reader.read_line(buf)?;
+
+        if bytes == 0 {
+            if let Some(file) = self.pending.take() {
+                self.reader = Box::new(BufReader::new(file));
+                if let Some(ref mut followed) = self.followed {
+                    followed.position = 0;
+                }
+                return self.read_line(buf);
+            }
+        }
+
+        if let Some(ref mut followed) = self.followed {
+            followed.position += bytes as u64;
+        }
+
+        Ok(bytes)
     }
 
     // Getter for first_line
     pub fn first_line(&self) -> &str {
         &self.first_line
     }
+
+    // Getter for name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+// Seeks the file to the start of the n-th line from its end, and returns that offset.
+// The file is read backwards in chunks, so that only the tail of a large file is read.
+fn offset_of_last_lines(file: &mut File, n: u64) -> io::Result<u64> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut remaining = n;
+    let mut end = len;
+    let mut chunk = vec![0; CHUNK_SIZE];
+
+    let offset = 'search: loop {
+        if remaining == 0 {
+            break end;
+        }
+        if end == 0 {
+            break 0;
+        }
+
+        let start = end.saturating_sub(CHUNK_SIZE as u64);
+        let size = (end - start) as usize;
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk[..size])?;
+
+        for i in (0..size).rev() {
+            let position = start + i as u64;
+            // A newline at the very end terminates the last line instead of starting a new one
+            if chunk[i] == b'\n' && position + 1 != len {
+                remaining -= 1;
+                if remaining == 0 {
+                    break 'search position + 1;
+                }
+            }
+        }
+        end = start;
+    };
+
+    file.seek(SeekFrom::Start(offset))?;
+    Ok(offset)
+}
+
+// Reads the reader till EOF, keeping only the last n lines.
+// This is used for inputs that cannot seek, like STDIN.
+fn last_lines<R: BufRead>(mut reader: R, n: u64) -> io::Result<Cursor<Vec<u8>>> {
+    let mut lines: VecDeque<Vec<u8>> = VecDeque::new();
+
+    loop {
+        let mut line = Vec::new();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        lines.push_back(line);
+        if lines.len() as u64 > n {
+            lines.pop_front();
+        }
+    }
+
+    Ok(Cursor::new(lines.into_iter().flatten().collect()))
+}
+
+// FollowedFile keeps track of the file behind a path being followed
+// id identifies the file currently open, so that a new file at the same path can be told apart.
+// position is the number of bytes read from the file currently open.
+// missing is set while the path is inaccessible.
+struct FollowedFile {
+    path: String,
+    follow: Follow,
+    id: Option<FileId>,
+    position: u64,
+    missing: bool,
+}
+
+impl FollowedFile {
+    fn new(path: &str, file: Option<&File>, follow: Follow) -> FollowedFile {
+        FollowedFile {
+            path: String::from(path),
+            follow,
+            id: file
+                .and_then(|file| file.metadata().ok())
+                .and_then(|m| file_id(&m)),
+            position: 0,
+            missing: file.is_none(),
+        }
+    }
+
+    // Reopens the file at the path if it has appeared. When following by name,
+    // it is also reopened if it was rotated or truncated.
+    // A notice is printed on STDERR whenever that happens.
+    fn reopen_if_changed(&mut self) -> Option<File> {
+        if self.follow != Follow::Name && !self.missing {
+            return None;
+        }
+
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                self.mark_missing(&err);
+                return None;
+            }
+        };
+
+        let id = file_id(&metadata);
+        let notice = if self.missing {
+            "has appeared; following new file"
+        } else if id != self.id {
+            "has been replaced; following new file"
+        } else if metadata.len() < self.position {
+            "has been truncated; following from the start"
+        } else {
+            return None;
+        };
+
+        match File::open(&self.path) {
+            Ok(file) => {
+                eprintln!("tztail: '{}' {}", self.path, notice);
+                self.id = id;
+                self.missing = false;
+                Some(file)
+            }
+            Err(err) => {
+                self.mark_missing(&err);
+                None
+            }
+        }
+    }
+
+    // Notifies only once that the file has gone missing
+    fn mark_missing(&mut self, err: &io::Error) {
+        if !self.missing {
+            eprintln!("tztail: '{}' has become inaccessible: {}", self.path, err);
+            self.missing = true;
+        }
+    }
+}
+
+// Identifies a file independent of its path
+type FileId = (u64, u64);
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+// Without a stable file identity, only truncation can be detected
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<FileId> {
+    None
 }
@@ -0,0 +1,263 @@
+// The formats tztail knows about. The default formats are detected anywhere in a line,
+// while presets are picked by name for the logs of a program.
+
+/// The default auto-detectable formats, used when no format is given
+// Add standard formats here
+// They get converted into Regexes and are validated
+// Seconds can have a fraction after a . or , with %.f. Digits beyond 9 are left out.
+pub const DEFAULT_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f%:z", // 2014-11-28T12:00:09.123456+05:30 RFC 3339, RFC 5424
+    "%Y-%m-%dT%H:%M:%S%.f%z",  // 2014-11-28T12:00:09.123456+0000 journald
+    "%Y-%m-%d %H:%M:%S%.f%z",  // 2014-11-28 12:00:09+0000
+    "%Y-%m-%dT%H:%M:%S%.f %z", // 2014-11-28T12:00:09 +0000
+    "%Y-%m-%d %H:%M:%S%.f %z", // 2014-11-28 12:00:09 +0000
+    "%d/%b/%Y:%H:%M:%S%.f %z", // 04/Nov/2018:12:13:49.334 +0000 Nginx
+    "%d/%b/%Y:%H:%M:%S%.f",    // 04/Nov/2018:12:13:49 HAProxy
+    "%a, %d %b %Y %H:%M:%S %z", // Fri, 28 Nov 2014 12:00:09 +0000
+    "%Y-%m-%dT%H:%M:%S%.f",    // 2014-11-28T12:00:09.279044
+    "%Y-%m-%d %H:%M:%S%.f",    // 2014-11-28 12:00:09,123 log4j
+];
+
+/// Presets detected along with the default formats, as their context tells them apart
+/// from other datetimes. Eg. syslog's `Nov  4 12:13:49` is only at the start of a line,
+/// while elsewhere it can be a part of `Sun Nov  4 12:13:49 2018`.
+pub const DEFAULT_PRESETS: &[&str] = &["klog", "syslog"];
+
+/// Preset is the format of the datetimes in the logs of a common program,
+/// along with the context they appear in so that other datetimes in a line are left alone.
+/// See [`Format::with_context`](struct.Format.html#method.with_context).
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Preset {
+    /// The name to pick the preset with
+    pub name: &'static str,
+    /// The strftime format of the datetime
+    pub format: &'static str,
+    /// A regex for what comes right before the datetime
+    pub context: &'static str,
+    /// A line from the logs of the program
+    pub example: &'static str,
+}
+
+/// Presets for the logs of common programs
+// Datetimes without an offset are in the source timezone
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "apache-error",
+        format: "%a %b %d %H:%M:%S%.f %Y",
+        context: r"^\[",
+        example: "[Sun Nov 04 12:13:49.334012 2018] [core:error] [pid 42] AH00126: Invalid URI",
+    },
+    Preset {
+        name: "nginx",
+        format: "%d/%b/%Y:%H:%M:%S%.f %z",
+        context: r"\[",
+        example: "10.0.0.1 - - [04/Nov/2018:12:13:49 +0000] \"GET / HTTP/1.1\" 200 612 \"-\" \"curl/7.54.0\"",
+    },
+    Preset {
+        name: "haproxy",
+        format: "%d/%b/%Y:%H:%M:%S%.f",
+        context: r"\]: \S+ \[",
+        example: "Nov  4 12:13:49 lb haproxy[42]: 10.0.0.1:51234 [04/Nov/2018:12:13:49.334] www api/web1 0/0/1/2/3 200 612",
+    },
+    Preset {
+        name: "log4j",
+        format: "%Y-%m-%d %H:%M:%S%.f",
+        context: "^",
+        example: "2018-11-04 12:13:49,334 ERROR [main] c.e.Orders - retry at 2018-11-04 12:14:49",
+    },
+    Preset {
+        name: "python-logging",
+        format: "%Y-%m-%d %H:%M:%S%.f",
+        context: "^",
+        example: "2018-11-04 12:13:49,334 - orders - WARNING - retry at 2018-11-04 12:14:49",
+    },
+    Preset {
+        name: "go-log",
+        format: "%Y/%m/%d %H:%M:%S%.f",
+        context: "^",
+        example: "2018/11/04 12:13:49.334012 server.go:42: listening on :8080",
+    },
+    Preset {
+        name: "klog",
+        format: "%m%d %H:%M:%S%.f",
+        // The severity letter of glog headers, after the prefix of kubectl logs --prefix
+        context: r"^(?:\[\S+\] )?[IWEF]",
+        example: "I1104 12:13:49.334012   12345 controller.go:42] synced at 2018-11-04 12:13:49",
+    },
+    Preset {
+        name: "docker",
+        format: "%Y-%m-%dT%H:%M:%S%.f%:z",
+        context: "^",
+        example: "2018-11-04T12:13:49.334012345Z listening at 2018-11-04T12:13:49Z",
+    },
+    Preset {
+        name: "postgres",
+        // The timezone of log_timezone, as a name like UTC or an abbreviation of the source timezone
+        format: "%Y-%m-%d %H:%M:%S%.f %Z",
+        context: "^",
+        example: "2018-11-04 12:13:49.334 UTC [12345] LOG:  checkpoint starting: time",
+    },
+    Preset {
+        name: "mysql",
+        format: "%Y-%m-%dT%H:%M:%S%.f%:z",
+        context: "^",
+        example: "2018-11-04T12:13:49.334012Z 0 [Note] mysqld: ready for connections.",
+    },
+    Preset {
+        name: "redis",
+        format: "%d %b %Y %H:%M:%S%.f",
+        context: r"^\d+:[XCSM] ",
+        example: "12345:M 04 Nov 2018 12:13:49.334 * Ready to accept connections",
+    },
+    Preset {
+        name: "syslog",
+        format: "%b %e %H:%M:%S%.f",
+        context: r"^(?:<\d+>)?",
+        example: "<34>Nov  4 12:13:49 host sshd[42]: accepted at Nov  4 12:13:48",
+    },
+];
+
+impl Preset {
+    /// Finds the preset with the given name
+    pub fn find(name: &str) -> Option<&'static Preset> {
+        PRESETS.iter().find(|preset| preset.name == name)
+    }
+}
+
+#[cfg(test)]
+mod registry_tests {
+    use super::{Preset, DEFAULT_FORMATS, DEFAULT_PRESETS, PRESETS};
+    use converter::Converter;
+    use format::Format;
+
+    #[test]
+    fn test_default_formats() {
+        for fmt in DEFAULT_FORMATS {
+            assert!(Format::new(fmt).is_ok(), "{}", fmt);
+        }
+        for name in DEFAULT_PRESETS {
+            assert!(Preset::find(name).is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_presets() {
+        // Only the datetime of the preset is converted, even when there are others in the line
+        let testcases = vec![
+            ("apache-error", "Sun Nov 04 12:13:49.334012 2018"),
+            ("nginx", "04/Nov/2018:12:13:49 +0000"),
+            ("haproxy", "04/Nov/2018:12:13:49.334"),
+            ("log4j", "2018-11-04 12:13:49,334"),
+            ("python-logging", "2018-11-04 12:13:49,334"),
+            ("go-log", "2018/11/04 12:13:49.334012"),
+            ("klog", "1104 12:13:49.334012"),
+            ("docker", "2018-11-04T12:13:49.334012345Z"),
+            ("postgres", "2018-11-04 12:13:49.334 UTC"),
+            ("mysql", "2018-11-04T12:13:49.334012Z"),
+            ("redis", "04 Nov 2018 12:13:49.334"),
+            ("syslog", "Nov  4 12:13:49"),
+        ];
+        assert_eq!(testcases.len(), PRESETS.len());
+
+        for (name, datetime) in testcases {
+            let preset = Preset::find(name).unwrap();
+            let converter = Converter::builder()
+                .timezone("UTC")
+                .preset(name)
+                .build()
+                .unwrap();
+
+            let output = converter.convert(preset.example);
+            assert!(output.errors.is_empty(), "{}: {:?}", name, output.errors);
+            let found: Vec<&str> = output
+                .spans
+                .iter()
+                .map(|span| span.original_time.as_str())
+                .collect();
+            assert_eq!(found, vec![datetime], "{}", name);
+            assert_eq!(output.spans[0].target_time, datetime, "{}", name);
+        }
+
+        assert_eq!(Preset::find("unknown"), None);
+    }
+
+    #[test]
+    fn test_postgres() {
+        // The timezone name is converted along with the datetime
+        let testcases = vec![
+            (
+                "2018-11-04 12:13:49.334 UTC [12345] LOG:  checkpoint starting: time",
+                "Asia/Kolkata",
+                None,
+                "2018-11-04 17:43:49.334 IST",
+            ),
+            (
+                "2018-11-04 17:43:49 IST [12345] LOG:  database system is ready",
+                "UTC",
+                Some("Asia/Kolkata"),
+                "2018-11-04 12:13:49 UTC",
+            ),
+            (
+                "2018-07-04 12:13:49 CEST [12345] LOG:  database system is ready",
+                "Asia/Kolkata",
+                Some("Europe/Berlin"),
+                "2018-07-04 15:43:49 IST",
+            ),
+        ];
+
+        for (input, timezone, source_timezone, expected) in testcases {
+            let mut builder = Converter::builder();
+            builder.timezone(timezone).preset("postgres");
+            if let Some(source_timezone) = source_timezone {
+                builder.source_timezone(source_timezone);
+            }
+            let output = builder.build().unwrap().convert(input);
+            assert!(output.errors.is_empty(), "{}: {:?}", input, output.errors);
+            assert_eq!(output.spans[0].target_time, expected, "{}", input);
+        }
+
+        // An abbreviation of another timezone than the source timezone cannot be converted
+        let output = Converter::builder()
+            .timezone("UTC")
+            .preset("postgres")
+            .build()
+            .unwrap()
+            .convert("2018-11-04 17:43:49 IST [12345] LOG:  database system is ready");
+        assert!(output.spans.is_empty());
+        assert_eq!(output.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_default_presets() {
+        let converter = Converter::builder().timezone("UTC").build().unwrap();
+
+        // Syslog timestamps are only found at the start of a line, after an optional <priority>
+        let testcases = vec![
+            (
+                "Nov  4 12:13:49 host sshd[42]: accepted",
+                vec!["Nov  4 12:13:49"],
+            ),
+            (
+                "<34>Nov  4 12:13:49 host sshd[42]: accepted",
+                vec!["Nov  4 12:13:49"],
+            ),
+            ("Mon Dec 31 23:00:00 2018 cron ran", vec![]),
+            (
+                "[Sun Nov 04 22:13:49.334012 2018] [core:error] [pid 42] failed",
+                vec![],
+            ),
+            ("Job 12 10:00:00 finished", vec![]),
+        ];
+
+        for (input, expected) in testcases {
+            let output = converter.convert(input);
+            assert!(output.errors.is_empty(), "{}: {:?}", input, output.errors);
+            let found: Vec<&str> = output
+                .spans
+                .iter()
+                .map(|span| span.original_time.as_str())
+                .collect();
+            assert_eq!(found, expected, "{}", input);
+        }
+    }
+}
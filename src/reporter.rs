@@ -0,0 +1,81 @@
+use args::ParseErrorPolicy;
+use tztail::{Error, TimedLog};
+
+// ParseErrorReporter counts the datetimes that could not be parsed and
+// reports them according to the ParseErrorPolicy, instead of on every line.
+pub struct ParseErrorReporter {
+    policy: ParseErrorPolicy,
+    count: usize,
+}
+
+impl ParseErrorReporter {
+    pub fn new(policy: ParseErrorPolicy) -> ParseErrorReporter {
+        ParseErrorReporter { policy, count: 0 }
+    }
+
+    // Checks a converted line for parse errors. Returns an error when the policy is to fail.
+    pub fn check(&mut self, log: &TimedLog) -> Result<(), Error> {
+        let first = match log.errors.first() {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+
+        match self.policy {
+            ParseErrorPolicy::Fail => return Err(Error::Parse(first.clone())),
+            ParseErrorPolicy::Warn if self.count == 0 => {
+                eprintln!("tztail: {}. Further errors are only counted", first)
+            }
+            _ => (),
+        }
+
+        self.count += log.errors.len();
+        Ok(())
+    }
+
+    // Reports how many datetimes could not be parsed, once there are no more lines
+    pub fn finish(&self) {
+        if self.policy == ParseErrorPolicy::Warn && self.count > 0 {
+            eprintln!("tztail: {} datetimes could not be parsed", self.count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod reporter_tests {
+    use super::ParseErrorReporter;
+    use args::ParseErrorPolicy;
+    use tztail::{Error, ParseError, TimedLog};
+
+    fn log_with_errors(n: usize) -> TimedLog<'static> {
+        TimedLog {
+            log: "2018-11-04 01:30:00 ambiguous",
+            converted: false,
+            spans: vec![],
+            errors: (0..n)
+                .map(|_| ParseError {
+                    datetime: String::from("2018-11-04 01:30:00"),
+                    reason: String::from("ambiguous or does not exist in America/New_York"),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let mut reporter = ParseErrorReporter::new(ParseErrorPolicy::Warn);
+        assert!(reporter.check(&log_with_errors(0)).is_ok());
+        assert!(reporter.check(&log_with_errors(2)).is_ok());
+        assert!(reporter.check(&log_with_errors(1)).is_ok());
+        assert_eq!(reporter.count, 3);
+
+        let mut reporter = ParseErrorReporter::new(ParseErrorPolicy::Ignore);
+        assert!(reporter.check(&log_with_errors(1)).is_ok());
+
+        let mut reporter = ParseErrorReporter::new(ParseErrorPolicy::Fail);
+        assert!(reporter.check(&log_with_errors(0)).is_ok());
+        match reporter.check(&log_with_errors(1)) {
+            Err(Error::Parse(err)) => assert_eq!(err.datetime, "2018-11-04 01:30:00"),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+}
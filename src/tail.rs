@@ -0,0 +1,90 @@
+use reader::InputReader;
+use std::io;
+use std::mem;
+use watcher::FileWatcher;
+
+// Tail reads lines from several inputs, like tail does with multiple files.
+// An input is read till EOF before moving on to the next one. Once all inputs
+// reach EOF, it waits for any of the followed inputs to change.
+//
+// partials holds the line being read from each input. While following, a line without
+// a newline is still being written, so it is kept until the rest of it arrives.
+// watcher is only set when at least one input is followed.
+pub struct Tail<'a> {
+    readers: Vec<InputReader<'a>>,
+    partials: Vec<String>,
+    current: usize,
+    watcher: Option<FileWatcher>,
+}
+
+impl<'a> Tail<'a> {
+    // Instantiate Tail from the readers of all inputs
+    pub fn new(readers: Vec<InputReader<'a>>) -> Tail<'a> {
+        let partials = readers
+            .iter()
+            .map(|reader| String::from(reader.first_line()))
+            .collect();
+
+        let watcher = if readers.iter().any(|reader| reader.is_followed()) {
+            let mut watcher = FileWatcher::new();
+            for reader in &readers {
+                reader.watch(&mut watcher);
+            }
+            Some(watcher)
+        } else {
+            None
+        };
+
+        Tail {
+            readers,
+            partials,
+            current: 0,
+            watcher,
+        }
+    }
+
+    // Reads the next line into buf and returns the index of the input it was read from.
+    // While following, this blocks until a line is available.
+    // Returns None once no more lines can arrive.
+    pub fn next_line(&mut self, buf: &mut String) -> io::Result<Option<usize>> {
+        loop {
+            for _ in 0..self.readers.len() {
+                let i = self.current;
+                if self.fill_line(i)? {
+                    buf.clear();
+                    mem::swap(buf, &mut self.partials[i]);
+                    return Ok(Some(i));
+                }
+                self.current = (self.current + 1) % self.readers.len();
+            }
+
+            match self.watcher {
+                Some(ref watcher) => watcher.wait(),
+                None => return Ok(None),
+            }
+
+            for reader in &mut self.readers {
+                reader.refresh();
+            }
+        }
+    }
+
+    // Reads from the i-th input until a line is complete.
+    // Returns true if the line is ready to be emitted.
+    fn fill_line(&mut self, i: usize) -> io::Result<bool> {
+        let reader = &mut self.readers[i];
+        let partial = &mut self.partials[i];
+
+        if !partial.ends_with('\n') {
+            reader.read_line(partial)?;
+        }
+
+        // The last line of an input that is not followed might not end with a newline
+        Ok(partial.ends_with('\n') || (!partial.is_empty() && !reader.is_followed()))
+    }
+
+    // Name of the i-th input
+    pub fn name(&self, i: usize) -> &str {
+        self.readers[i].name()
+    }
+}
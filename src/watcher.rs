@@ -0,0 +1,74 @@
+use notify::{self, RawEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+// How long to wait for a change notification before checking the files anyway.
+// This is also the polling interval when notifications are not available.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// FileWatcher blocks until any of the watched files change, so that follow mode does not spin at EOF.
+//
+// It relies on filesystem notifications (inotify on Linux) when available.
+// Waiting always times out after POLL_INTERVAL, so filesystems that never deliver
+// notifications (Eg. network filesystems) still get polled.
+// If notifications cannot be set up at all, it falls back to plain polling.
+pub struct FileWatcher {
+    // Held to keep the watches alive. None when falling back to polling.
+    watcher: Option<RecommendedWatcher>,
+    events: Receiver<RawEvent>,
+}
+
+impl Default for FileWatcher {
+    fn default() -> FileWatcher {
+        let (tx, events) = channel();
+
+        FileWatcher {
+            watcher: notify::raw_watcher(tx).ok(),
+            events,
+        }
+    }
+}
+
+impl FileWatcher {
+    // Instantiate a FileWatcher that is not watching anything yet
+    pub fn new() -> FileWatcher {
+        FileWatcher::default()
+    }
+
+    // Starts watching the given path.
+    // Failing to watch is not an error as the path still gets polled.
+    pub fn watch<P: AsRef<Path>>(&mut self, path: P) {
+        if let Some(ref mut watcher) = self.watcher {
+            let _ = watcher.watch(path, RecursiveMode::NonRecursive);
+        }
+    }
+
+    // Starts watching the directory containing the given path.
+    // Unlike watching the file itself, this notices the file being created, renamed or deleted.
+    pub fn watch_parent(&mut self, path: &str) {
+        match Path::new(path).parent() {
+            Some(parent) if parent != Path::new("") => self.watch(parent),
+            _ => self.watch("."),
+        }
+    }
+
+    // Blocks until a watched path changes or POLL_INTERVAL passes
+    pub fn wait(&self) {
+        if self.watcher.is_none() {
+            thread::sleep(POLL_INTERVAL);
+            return;
+        }
+
+        match self.events.recv_timeout(POLL_INTERVAL) {
+            Ok(_) => {
+                // A single write can fire several events. Drain them so that
+                // the next wait blocks until something new happens.
+                while self.events.try_recv().is_ok() {}
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(POLL_INTERVAL),
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use tztail::{Converter, Error, ParseError};
+
+// Formats accepted for absolute times in --since and --until
+const DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+];
+
+// Formats accepted for times of the current day in --since and --until
+const TIME_FORMATS: &[&str] = &["%H:%M:%S", "%H:%M"];
+
+// TimeWindow filters lines to the ones with an instant between since and until, both inclusive.
+// Lines without a timestamp go along with the line before them.
+pub struct TimeWindow {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl TimeWindow {
+    // Instantiate TimeWindow based on cli options chosen.
+    // Absolute times without a timezone are taken to be in `timezone`, or the local timezone if not given.
+    pub fn new(
+        since: Option<&str>,
+        until: Option<&str>,
+        timezone: Option<Tz>,
+    ) -> Result<TimeWindow, Error> {
+        let now = Utc::now();
+        let parse = |spec: Option<&str>| match spec {
+            Some(spec) => parse_time(spec, timezone, now)
+                .map(Some)
+                .map_err(Error::Parse),
+            None => Ok(None),
+        };
+
+        Ok(TimeWindow {
+            since: parse(since)?,
+            until: parse(until)?,
+        })
+    }
+
+    // Decides if a line with the given instant is to be shown.
+    // `shown` is whether the previous line of the same input was shown, and is updated for the next line.
+    pub fn admits(&self, instant: Option<DateTime<Utc>>, shown: &mut bool) -> bool {
+        if let Some(instant) = instant {
+            *shown = self.since.map_or(true, |since| instant >= since)
+                && self.until.map_or(true, |until| instant <= until);
+        }
+        *shown
+    }
+}
+
+// Parses a time given on the command line. It can either be
+//
+// 1. Relative to now. Eg. now, -30s, -15m, -2h, -1d
+// 2. RFC 3339. Eg. 2018-11-21T14:00:00+05:30
+// 3. A date and time, a date or a time of today, optionally followed by a timezone.
+//    Eg. "2018-11-21 14:00", "2018-11-21", "14:00:00 Asia/Kolkata"
+pub fn parse_time(
+    spec: &str,
+    timezone: Option<Tz>,
+    now: DateTime<Utc>,
+) -> Result<DateTime<Utc>, ParseError> {
+    let spec = spec.trim();
+    let invalid = || ParseError {
+        datetime: String::from(spec),
+        reason: String::from(
+            "expected a relative time like -15m or a datetime like 2018-11-21 14:00",
+        ),
+    };
+
+    if spec == "now" {
+        return Ok(now);
+    }
+
+    if let Some(relative) = spec.strip_prefix('-') {
+        return parse_duration(relative)
+            .map(|duration| now - duration)
+            .ok_or_else(invalid);
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(spec) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+
+    // A trailing timezone name overrides the target timezone
+    let (spec, timezone) = match spec.rfind(' ') {
+        Some(i) => match spec[i + 1..].parse::<Tz>() {
+            Ok(tz) => (spec[..i].trim_end(), Some(tz)),
+            Err(_) => (spec, timezone),
+        },
+        None => (spec, timezone),
+    };
+
+    let instant = match timezone {
+        Some(tz) => from_local(&tz, spec, now),
+        None => from_local(&Local, spec, now),
+    };
+    instant.ok_or_else(invalid)
+}
+
+// Parses a duration like 30s, 15m, 2h or 1d
+fn parse_duration(spec: &str) -> Option<Duration> {
+    if spec.len() < 2 {
+        return None;
+    }
+
+    let (amount, unit) = spec.split_at(spec.len() - 1);
+    let amount: i64 = amount.parse().ok()?;
+    match unit {
+        "s" => Some(Duration::seconds(amount)),
+        "m" => Some(Duration::minutes(amount)),
+        "h" => Some(Duration::hours(amount)),
+        "d" => Some(Duration::days(amount)),
+        _ => None,
+    }
+}
+
+// Parses a local time in the given timezone.
+// Ambiguous local times resolve to the earlier instant.
+fn from_local<T: TimeZone>(tz: &T, spec: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let today = now.with_timezone(tz).naive_local().date();
+    let naive = parse_naive(spec, today)?;
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+fn parse_naive(spec: &str, today: NaiveDate) -> Option<NaiveDateTime> {
+    for fmt in DATETIME_FORMATS {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(spec, fmt) {
+            return Some(dt);
+        }
+    }
+
+    for fmt in TIME_FORMATS {
+        if let Ok(time) = NaiveTime::parse_from_str(spec, fmt) {
+            return Some(today.and_time(time));
+        }
+    }
+
+    NaiveDate::parse_from_str(spec, "%Y-%m-%d")
+        .ok()
+        .map(|date| date.and_hms(0, 0, 0))
+}
+
+// Finds the offset of the first line in the file with an instant at or after `since`,
+// by binary searching the file instead of reading it from the start.
+// This expects the lines of the file to be in chronological order.
+pub fn offset_of_since(path: &str, converter: &Converter, since: DateTime<Utc>) -> io::Result<u64> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut low = 0;
+    let mut high = reader.get_ref().metadata()?.len();
+
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match next_instant(&mut reader, mid, converter)? {
+            Some((start, instant)) if instant < since => low = start + 1,
+            _ => high = mid,
+        }
+    }
+
+    line_start(&mut reader, low)
+}
+
+// Finds the first line with a timestamp that starts at or after offset.
+// Returns where that line starts along with its instant.
+fn next_instant(
+    reader: &mut BufReader<File>,
+    offset: u64,
+    converter: &Converter,
+) -> io::Result<Option<(u64, DateTime<Utc>)>> {
+    let mut position = line_start(reader, offset)?;
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        let bytes = reader.read_until(b'\n', &mut line)?;
+        if bytes == 0 {
+            return Ok(None);
+        }
+
+        if let Some(instant) = converter.convert(&String::from_utf8_lossy(&line)).instant() {
+            return Ok(Some((position, instant)));
+        }
+        position += bytes as u64;
+    }
+}
+
+// Seeks to the first line that starts at or after offset, and returns where it starts
+fn line_start(reader: &mut BufReader<File>, offset: u64) -> io::Result<u64> {
+    if offset == 0 {
+        return reader.seek(SeekFrom::Start(0));
+    }
+
+    // Offset is a line start if the byte before it is a newline
+    reader.seek(SeekFrom::Start(offset - 1))?;
+    let mut skipped = Vec::new();
+    let bytes = reader.read_until(b'\n', &mut skipped)?;
+    Ok(offset - 1 + bytes as u64)
+}
+
+#[cfg(test)]
+mod window_tests {
+    use chrono::{TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    #[test]
+    fn test_parse_time() {
+        let now = Utc.ymd(2018, 11, 21).and_hms(12, 0, 0);
+        let kolkata: Tz = "Asia/Kolkata".parse().unwrap();
+
+        let testcases = vec![
+            ("now", None, Utc.ymd(2018, 11, 21).and_hms(12, 0, 0)),
+            ("-15m", None, Utc.ymd(2018, 11, 21).and_hms(11, 45, 0)),
+            ("-2h", None, Utc.ymd(2018, 11, 21).and_hms(10, 0, 0)),
+            ("-1d", None, Utc.ymd(2018, 11, 20).and_hms(12, 0, 0)),
+            (
+                "2018-11-21T14:00:00+05:30",
+                None,
+                Utc.ymd(2018, 11, 21).and_hms(8, 30, 0),
+            ),
+            (
+                "2018-11-21 14:00",
+                Some(kolkata),
+                Utc.ymd(2018, 11, 21).and_hms(8, 30, 0),
+            ),
+            (
+                "2018-11-21 14:00:30 UTC",
+                Some(kolkata),
+                Utc.ymd(2018, 11, 21).and_hms(14, 0, 30),
+            ),
+            (
+                "2018-11-21",
+                Some(kolkata),
+                Utc.ymd(2018, 11, 20).and_hms(18, 30, 0),
+            ),
+            (
+                "14:30",
+                Some(kolkata),
+                Utc.ymd(2018, 11, 21).and_hms(9, 0, 0),
+            ),
+            (
+                "09:00 America/New_York",
+                Some(kolkata),
+                Utc.ymd(2018, 11, 21).and_hms(14, 0, 0),
+            ),
+        ];
+
+        for (spec, timezone, expected) in testcases {
+            assert_eq!(
+                super::parse_time(spec, timezone, now),
+                Ok(expected),
+                "{}",
+                spec
+            );
+        }
+
+        assert!(super::parse_time("-15x", None, now).is_err());
+        assert!(super::parse_time("yesterday", None, now).is_err());
+    }
+
+    #[test]
+    fn test_admits() {
+        let window = super::TimeWindow {
+            since: Some(Utc.ymd(2018, 11, 21).and_hms(14, 0, 0)),
+            until: Some(Utc.ymd(2018, 11, 21).and_hms(14, 30, 0)),
+        };
+        let mut shown = false;
+
+        assert!(!window.admits(None, &mut shown));
+        assert!(!window.admits(Some(Utc.ymd(2018, 11, 21).and_hms(13, 59, 59)), &mut shown));
+        assert!(window.admits(Some(Utc.ymd(2018, 11, 21).and_hms(14, 0, 0)), &mut shown));
+        // Lines without a timestamp follow the line before them
+        assert!(window.admits(None, &mut shown));
+        assert!(window.admits(Some(Utc.ymd(2018, 11, 21).and_hms(14, 30, 0)), &mut shown));
+        assert!(!window.admits(Some(Utc.ymd(2018, 11, 21).and_hms(14, 30, 1)), &mut shown));
+        assert!(!window.admits(None, &mut shown));
+    }
+}
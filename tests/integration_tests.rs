@@ -10,7 +10,10 @@ use chrono::prelude::*;
 use chrono::DateTime;
 
 use escargot::CargoRun;
-use std::process::Command;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
 
 lazy_static! {
     static ref CARGO_RUN: CargoRun = escargot::CargoBuild::new()
@@ -24,6 +27,7 @@ fn tztail() -> Command {
     CARGO_RUN.command()
 }
 
+#[allow(clippy::needless_return)]
 fn convert_to_localtimezone(input: &str, format: &str) -> String {
     let local_timezone = Local::now().timezone();
     return DateTime::parse_from_str(input, format)
@@ -72,7 +76,8 @@ fn test_read_from_file() {
         .stdout(format!(
             "{} postgres parameters not changed\n{} postgres parameters not changed",
             local_time.0, local_time.1,
-        )).stderr("");
+        ))
+        .stderr("");
 }
 
 #[test]
@@ -104,3 +109,828 @@ fn test_default_utc() {
         .stdout("2018/11/22T04:18:14: This is a log in UTC")
         .stderr("");
 }
+
+#[test]
+fn test_multiple_timestamps() {
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .with_stdin()
+        .buffer("[04/Nov/2018:12:13:49 +0000] upstream at 2018-11-04T12:13:48+0000 took 1s")
+        .assert()
+        .success()
+        .stdout("[04/Nov/2018:17:43:49 +0530] upstream at 2018-11-04T17:43:48+0530 took 1s")
+        .stderr("");
+}
+
+#[test]
+fn test_source_timezone() {
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("--source-timezone")
+        .arg("America/New_York")
+        .with_stdin()
+        .buffer("2018-07-01 10:00:00 request served\n2018-11-04 01:30:00 clocks turned back")
+        .assert()
+        .success()
+        .stdout("2018-07-01 19:30:00 request served\n2018-11-04 11:00:00* clocks turned back")
+        .stderr("");
+}
+
+#[test]
+fn test_output_format() {
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("--output-format")
+        .arg("%Y-%m-%d %H:%M:%S %z")
+        .with_stdin()
+        .buffer("2018-11-03 22:39:33 This is a log in UTC")
+        .assert()
+        .success()
+        .stdout("2018-11-04 04:09:33 +0530 This is a log in UTC")
+        .stderr("");
+}
+
+#[test]
+fn test_follow_file() {
+    let path = env::temp_dir().join(format!("tztail_test_follow_{}.log", std::process::id()));
+    fs::write(&path, "2018-11-21T17:26:30+0700 first\n").unwrap();
+
+    let mut child = tztail()
+        .arg("-t")
+        .arg("UTC")
+        .arg("-f")
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!(line, "2018-11-21T10:26:30+0000 first\n");
+
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(b"2018-11-21T18:26:30+0700 appended\n")
+        .unwrap();
+
+    line.clear();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!(line, "2018-11-21T11:26:30+0000 appended\n");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_follow_name_across_rotation() {
+    let dir = env::temp_dir().join(format!("tztail_test_rotation_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("app.log");
+
+    let mut child = tztail()
+        .arg("-t")
+        .arg("UTC")
+        .arg("-F")
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    // Waits for the file to be created
+    fs::write(&path, "2018-11-21T17:26:30+0700 created\n").unwrap();
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!(line, "2018-11-21T10:26:30+0000 created\n");
+
+    // Rotates by renaming and creating a new file
+    fs::rename(&path, dir.join("app.log.1")).unwrap();
+    fs::write(&path, "2018-11-21T17:26:31+0700 rotated\n").unwrap();
+    line.clear();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!(line, "2018-11-21T10:26:31+0000 rotated\n");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_multiple_files() {
+    let dir = env::temp_dir().join(format!("tztail_test_multiple_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let app = dir.join("app.log");
+    let worker = dir.join("worker.log");
+    fs::write(&app, "2018-11-21T17:26:30+0700 app started\n").unwrap();
+    fs::write(&worker, "2018-11-21T17:26:31+0700 worker started").unwrap();
+
+    tztail()
+        .arg("-t")
+        .arg("UTC")
+        .arg(&app)
+        .arg(&worker)
+        .assert()
+        .success()
+        .stdout(format!(
+            "==> {} <==\n2018-11-21T10:26:30+0000 app started\n\n==> {} <==\n2018-11-21T10:26:31+0000 worker started\n",
+            app.display(),
+            worker.display()
+        )).stderr("");
+
+    tztail()
+        .arg("-t")
+        .arg("UTC")
+        .arg("--prefix")
+        .arg(&app)
+        .arg(&worker)
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}: 2018-11-21T10:26:30+0000 app started\n{}: 2018-11-21T10:26:31+0000 worker started\n",
+            app.display(),
+            worker.display()
+        )).stderr("");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_follow_multiple_files() {
+    let dir = env::temp_dir().join(format!(
+        "tztail_test_follow_multiple_{}",
+        std::process::id()
+    ));
+    fs::create_dir_all(&dir).unwrap();
+    let app = dir.join("app.log");
+    let worker = dir.join("worker.log");
+    fs::write(&app, "").unwrap();
+    fs::write(&worker, "").unwrap();
+
+    let mut child = tztail()
+        .arg("-t")
+        .arg("UTC")
+        .arg("-f")
+        .arg("--prefix")
+        .arg(&app)
+        .arg(&worker)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    let mut line = String::new();
+    for (path, message) in &[
+        (&worker, "worker"),
+        (&app, "app"),
+        (&worker, "worker again"),
+    ] {
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        writeln!(file, "2018-11-21T17:26:30+0700 {}", message).unwrap();
+
+        line.clear();
+        stdout.read_line(&mut line).unwrap();
+        assert_eq!(
+            line,
+            format!("{}: 2018-11-21T10:26:30+0000 {}\n", path.display(), message)
+        );
+    }
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    // STDIN would hold up the files while waiting for a line
+    tztail()
+        .arg("-f")
+        .arg(&app)
+        .arg("-")
+        .with_stdin()
+        .buffer("")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr("Exited non-successfully: STDIN cannot be followed along with other files, as reading it holds them up\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_merge() {
+    let dir = env::temp_dir().join(format!("tztail_test_merge_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let api = dir.join("api.log");
+    let worker = dir.join("worker.log");
+    fs::write(
+        &api,
+        "2018-11-21 10:00:00 api started\n2018-11-21 10:00:05 api failed\n  at handler()\n2018-11-21 10:00:09 api stopped\n",
+    ).unwrap();
+    fs::write(
+        &worker,
+        "2018-11-21 15:30:02 worker started\n2018-11-21 15:30:07 worker stopped\n",
+    )
+    .unwrap();
+
+    tztail()
+        .arg("-t")
+        .arg("UTC")
+        .arg("--merge")
+        .arg("--prefix")
+        .arg("--source-timezone")
+        .arg("UTC")
+        .arg("--source-timezone")
+        .arg("Asia/Kolkata")
+        .arg(&api)
+        .arg(&worker)
+        .assert()
+        .success()
+        .stdout(format!(
+            "{api}: 2018-11-21 10:00:00 api started\n\
+                 {worker}: 2018-11-21 10:00:02 worker started\n\
+                 {api}: 2018-11-21 10:00:05 api failed\n\
+                 {api}:   at handler()\n\
+                 {worker}: 2018-11-21 10:00:07 worker stopped\n\
+                 {api}: 2018-11-21 10:00:09 api stopped\n",
+            api = api.display(),
+            worker = worker.display()
+        ))
+        .stderr("");
+
+    // The header of STDIN is read before the lines after it are merged
+    let csv = dir.join("jobs.csv");
+    fs::write(
+        &csv,
+        "time,job\n2018-11-21 10:00:01,backup\n2018-11-21 10:00:05,report\n",
+    )
+    .unwrap();
+
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("--merge")
+        .arg("--prefix")
+        .arg("--column")
+        .arg("time")
+        .arg(&csv)
+        .arg("-")
+        .with_stdin()
+        .buffer("time,job\n2018-11-21 10:00:03,cleanup\n")
+        .assert()
+        .success()
+        .stdout(format!(
+            "{csv}: time,job\n\
+                 standard input: time,job\n\
+                 {csv}: 2018-11-21 15:30:01,backup\n\
+                 standard input: 2018-11-21 15:30:03,cleanup\n\
+                 {csv}: 2018-11-21 15:30:05,report\n",
+            csv = csv.display()
+        ))
+        .stderr("");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_lines() {
+    let dir = env::temp_dir().join(format!("tztail_test_lines_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("big.log");
+
+    // Large enough to be read backwards in several chunks
+    let content: String = (1..=20000)
+        .map(|i| format!("2018-11-21 10:00:00 line {}\n", i))
+        .collect();
+    fs::write(&path, content).unwrap();
+
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("-n")
+        .arg("2")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout("2018-11-21 15:30:00 line 19999\n2018-11-21 15:30:00 line 20000\n")
+        .stderr("");
+
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("-n")
+        .arg("+19999")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout("2018-11-21 15:30:00 line 19999\n2018-11-21 15:30:00 line 20000\n")
+        .stderr("");
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("-n")
+        .arg("1")
+        .with_stdin()
+        .buffer("2018-11-21 10:00:00 first\n2018-11-21 10:00:01 last")
+        .assert()
+        .success()
+        .stdout("2018-11-21 15:30:01 last")
+        .stderr("");
+}
+
+#[test]
+fn test_since_until() {
+    let dir = env::temp_dir().join(format!("tztail_test_since_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("app.log");
+
+    let content: String = (0..20000)
+        .map(|i| {
+            format!(
+                "2018-11-21 {:02}:{:02}:{:02} line {}\n  at frame {}\n",
+                i / 3600,
+                i / 60 % 60,
+                i % 60,
+                i,
+                i
+            )
+        })
+        .collect();
+    fs::write(&path, content).unwrap();
+
+    // Absolute times are read in the target timezone, unless a timezone is given
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("--since")
+        .arg("2018-11-21 08:30:00")
+        .arg("--until")
+        .arg("2018-11-21 03:00:01 UTC")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(
+            "2018-11-21 08:30:00 line 10800\n  at frame 10800\n\
+             2018-11-21 08:30:01 line 10801\n  at frame 10801\n",
+        )
+        .stderr("");
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    tztail()
+        .arg("-t")
+        .arg("UTC")
+        .arg("--since")
+        .arg("-15m")
+        .with_stdin()
+        .buffer("2018-11-21 10:00:00 old\n  at frame\n")
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+
+    tztail()
+        .arg("--since")
+        .arg("yesterday")
+        .with_stdin()
+        .buffer("")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_invalid_timezone() {
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkatta")
+        .with_stdin()
+        .buffer("")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr("Exited non-successfully: Invalid timezone 'Asia/Kolkatta'\n");
+}
+
+#[test]
+fn test_on_parse_error() {
+    // Clocks turned back at 02:00 EDT, so 01:30 happens twice
+    let input =
+        "2018-11-04 01:30:00 first\n2018-11-04 01:45:00 second\n2018-11-05 01:30:00 third\n";
+    let run = |policy: &str| {
+        tztail()
+            .arg("-t")
+            .arg("UTC")
+            .arg("--source-timezone")
+            .arg("America/New_York")
+            .arg("--ambiguous")
+            .arg("error")
+            .arg("--on-parse-error")
+            .arg(policy)
+            .with_stdin()
+            .buffer(input)
+            .assert()
+    };
+
+    run("ignore")
+        .success()
+        .stdout(
+            "2018-11-04 01:30:00 first\n2018-11-04 01:45:00 second\n2018-11-05 06:30:00 third\n",
+        )
+        .stderr("");
+
+    run("warn")
+        .success()
+        .stdout("2018-11-04 01:30:00 first\n2018-11-04 01:45:00 second\n2018-11-05 06:30:00 third\n")
+        .stderr(
+            "tztail: Could not parse '2018-11-04 01:30:00': ambiguous or does not exist in America/New_York. \
+             Further errors are only counted\n\
+             tztail: 2 datetimes could not be parsed\n",
+        );
+
+    run("fail").failure().stdout("").stderr(
+        "Exited non-successfully: Could not parse '2018-11-04 01:30:00': \
+         ambiguous or does not exist in America/New_York\n",
+    );
+}
+
+#[test]
+fn test_invalid_custom_format() {
+    tztail()
+        .arg("--format")
+        .arg("%Y-%m-%d %H:%M:%S %Q")
+        .with_stdin()
+        .buffer("")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(
+            "Exited non-successfully: Invalid format '%Y-%m-%d %H:%M:%S %Q': \
+             unknown or unsupported specifier '%Q' at position 19\n",
+        );
+}
+
+#[test]
+fn test_auto_lock() {
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("--auto-lock")
+        .arg("2")
+        .arg("--verbose")
+        .with_stdin()
+        .buffer(
+            "2018-11-21 10:00:00 started\n\
+             2018-11-21 10:00:01 served\n\
+             served at 21/Nov/2018:10:00:02 +0000\n",
+        )
+        .assert()
+        .success()
+        .stdout(
+            "2018-11-21 15:30:00 started\n\
+             2018-11-21 15:30:01 served\n\
+             served at 21/Nov/2018:15:30:02 +0530\n",
+        )
+        .stderr("tztail: standard input: locked onto format '%Y-%m-%d %H:%M:%S%.f'\n");
+}
+
+#[test]
+fn test_epoch() {
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("--epoch")
+        .arg("\"ts\":")
+        .arg("--output-format")
+        .arg("rfc3339")
+        .with_stdin()
+        .buffer(concat!(
+            "{\"ts\":1542794400123,\"id\":1542794400,\"msg\":\"served\"}\n",
+            "{\"ts\":\"1542794400\",\"msg\":\"served\"}\n",
+        ))
+        .assert()
+        .success()
+        .stdout(concat!(
+            "{\"ts\":\"2018-11-21T15:30:00.123+05:30\",\"id\":1542794400,\"msg\":\"served\"}\n",
+            "{\"ts\":\"2018-11-21T15:30:00+05:30\",\"msg\":\"served\"}\n",
+        ))
+        .stderr("");
+}
+
+#[test]
+fn test_json() {
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("--json")
+        .arg("--key")
+        .arg("ts")
+        .arg("--key")
+        .arg("meta.time")
+        .arg("--output-format")
+        .arg("rfc3339")
+        .with_stdin()
+        .buffer(concat!(
+            "{\"ts\": 1542794400, \"msg\": \"at 2018-11-21 10:00:00\", \"meta\": {\"time\": \"2018-11-21T10:00:00Z\"}}\n",
+            "2018-11-21 10:00:00 not json\n",
+        ))
+        .assert()
+        .success()
+        .stdout(concat!(
+            "{\"ts\": \"2018-11-21T15:30:00+05:30\", \"msg\": \"at 2018-11-21 10:00:00\", \"meta\": {\"time\": \"2018-11-21T15:30:00+05:30\"}}\n",
+            "2018-11-21 10:00:00 not json\n",
+        ))
+        .stderr("");
+}
+
+#[test]
+fn test_logfmt() {
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("--logfmt")
+        .with_stdin()
+        .buffer("ts=2024-01-02T03:04:05Z  level=info msg=\"retry at 2024-01-02T03:04:05Z\"\n")
+        .assert()
+        .success()
+        .stdout("ts=2024-01-02T08:34:05+05:30  level=info msg=\"retry at 2024-01-02T03:04:05Z\"\n")
+        .stderr("");
+
+    tztail()
+        .arg("--key")
+        .arg("ts")
+        .with_stdin()
+        .buffer("")
+        .assert()
+        .failure()
+        .stderr("Exited non-successfully: --key can only be used with --json or --logfmt\n");
+}
+
+#[test]
+fn test_column() {
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("--column")
+        .arg("created at")
+        .with_stdin()
+        .buffer(concat!(
+            "id,\"created at\",note\n",
+            "1,\"2018-11-21 10:00:00\",\"seen at 2018-11-21 10:00:00, twice\"\n",
+        ))
+        .assert()
+        .success()
+        .stdout(concat!(
+            "id,\"created at\",note\n",
+            "1,\"2018-11-21 15:30:00\",\"seen at 2018-11-21 10:00:00, twice\"\n",
+        ))
+        .stderr("");
+
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("--column")
+        .arg("2")
+        .arg("--delimiter")
+        .arg("tab")
+        .with_stdin()
+        .buffer("2018-11-21T10:00:00Z\t2018-11-21T10:00:00Z\n")
+        .assert()
+        .success()
+        .stdout("2018-11-21T10:00:00Z\t2018-11-21T15:30:00+05:30\n")
+        .stderr("");
+
+    tztail()
+        .arg("--column")
+        .arg("0")
+        .with_stdin()
+        .buffer("")
+        .assert()
+        .failure()
+        .stderr("Exited non-successfully: Invalid column '0': columns are counted from 1\n");
+
+    tztail()
+        .arg("--column")
+        .arg("1")
+        .arg("--delimiter")
+        .arg(";;")
+        .with_stdin()
+        .buffer("")
+        .assert()
+        .failure()
+        .stderr(
+            "Exited non-successfully: Invalid delimiter ';;': \
+             expected a single character other than '\"', tab or whitespace\n",
+        );
+}
+
+#[test]
+fn test_ambiguous_structured() {
+    // Clocks turned back at 02:00 EDT, so 01:30 happens twice.
+    // The mark for the resolved time is left out, as it would break the value.
+    let run = |mode: &[&str], input: &str, output: &str| {
+        tztail()
+            .arg("-t")
+            .arg("UTC")
+            .arg("--source-timezone")
+            .arg("America/New_York")
+            .args(mode)
+            .with_stdin()
+            .buffer(input)
+            .assert()
+            .success()
+            .stdout(output.to_string())
+            .stderr("");
+    };
+
+    run(
+        &["--json", "--key", "ts"],
+        "{\"ts\":\"2018-11-04 01:30:00\"}\n",
+        "{\"ts\":\"2018-11-04 05:30:00\"}\n",
+    );
+    run(
+        &["--logfmt"],
+        "ts=2018-11-04T01:30:00 msg=x\n",
+        "ts=2018-11-04T05:30:00 msg=x\n",
+    );
+    run(
+        &["--column", "2"],
+        "1,2018-11-04 01:30:00,x\n",
+        "1,2018-11-04 05:30:00,x\n",
+    );
+    run(&[], "2018-11-04 01:30:00 x\n", "2018-11-04 05:30:00* x\n");
+}
+
+#[test]
+fn test_syslog() {
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .with_stdin()
+        .buffer(concat!(
+            "Nov  4 12:13:49 host sshd[42]: accepted\n",
+            "Nov 30 20:00:00 host cron[1]: rotated\n",
+        ))
+        .assert()
+        .success()
+        .stdout(concat!(
+            "Nov  4 17:43:49 host sshd[42]: accepted\n",
+            "Dec  1 01:30:00 host cron[1]: rotated\n",
+        ))
+        .stderr("");
+}
+
+#[test]
+fn test_fractional_seconds() {
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .with_stdin()
+        .buffer(concat!(
+            "2018-11-03 19:47:20.279044 worker started\n",
+            "2018-11-03 12:00:09,123 INFO [main] started\n",
+        ))
+        .assert()
+        .success()
+        .stdout(concat!(
+            "2018-11-04 01:17:20.279044 worker started\n",
+            "2018-11-03 17:30:09,123 INFO [main] started\n",
+        ))
+        .stderr("");
+}
+
+#[test]
+fn test_preset() {
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("--preset")
+        .arg("nginx")
+        .with_stdin()
+        .buffer(concat!(
+            "10.0.0.1 - - [04/Nov/2018:12:13:49 +0000] \"GET /?at=04/Nov/2018:12:13:49 +0000 HTTP/1.1\" 200\n",
+            "2018-11-04 12:13:49 not an access log\n",
+        ))
+        .assert()
+        .success()
+        .stdout(concat!(
+            "10.0.0.1 - - [04/Nov/2018:17:43:49 +0530] \"GET /?at=04/Nov/2018:12:13:49 +0000 HTTP/1.1\" 200\n",
+            "2018-11-04 12:13:49 not an access log\n",
+        ))
+        .stderr("");
+
+    let output = tztail().arg("--list-presets").output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .contains("redis\n  format:  %d %b %Y %H:%M:%S%.f\n  context: ^\\d+:[XCSM] \n"));
+}
+
+#[test]
+fn test_klog() {
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .with_stdin()
+        .buffer(concat!(
+            "I1103 19:47:20.279044   12345 controller.go:42] synced\n",
+            "E1103 20:00:00.000001       1 reflector.go:7] watch failed\n",
+        ))
+        .assert()
+        .success()
+        .stdout(concat!(
+            "I1104 01:17:20.279044   12345 controller.go:42] synced\n",
+            "E1104 01:30:00.000001       1 reflector.go:7] watch failed\n",
+        ))
+        .stderr("");
+}
+
+#[test]
+fn test_uptime() {
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("--boot-time")
+        .arg("2018-11-04T12:00:00Z")
+        .with_stdin()
+        .buffer(concat!(
+            "[    0.000000] Linux version 4.19.0\n",
+            "<6>[  829.012345] usb 1-1: new device\n",
+        ))
+        .assert()
+        .success()
+        .stdout(concat!(
+            "[2018-11-04 17:30:00.000000] Linux version 4.19.0\n",
+            "<6>[2018-11-04 17:43:49.012345] usb 1-1: new device\n",
+        ))
+        .stderr("");
+
+    // The boot time is learnt from the anchor line
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("--boot-time")
+        .arg("anchor")
+        .with_stdin()
+        .buffer(concat!(
+            "[    1.500000] rtc_cmos 00:00: setting system clock to 2018-11-04T12:00:01 UTC\n",
+            "[  829.012345] usb 1-1: new device\n",
+            "[  900.000000] restored backup of 2018-01-01T00:00:00\n",
+            "[  901.000000] usb 1-1: new device\n",
+        ))
+        .assert()
+        .success()
+        .stdout(concat!(
+            "[2018-11-04 17:30:01.000000] rtc_cmos 00:00: setting system clock to 2018-11-04T17:30:01 IST\n",
+            "[2018-11-04 17:43:48.512345] usb 1-1: new device\n",
+            "[2018-11-04 17:44:59.500000] restored backup of 2018-01-01T05:30:00\n",
+            "[2018-11-04 17:45:00.500000] usb 1-1: new device\n",
+        ))
+        .stderr("");
+
+    // Datetimes after an uptime do not change a given boot time
+    tztail()
+        .arg("-t")
+        .arg("Asia/Kolkata")
+        .arg("--boot-time")
+        .arg("2018-11-04T12:00:00Z")
+        .with_stdin()
+        .buffer(concat!(
+            "[    1.500000] restored backup of 2018-01-01T00:00:00\n",
+            "[  829.012345] usb 1-1: new device\n",
+        ))
+        .assert()
+        .success()
+        .stdout(concat!(
+            "[2018-11-04 17:30:01.500000] restored backup of 2018-01-01T05:30:00\n",
+            "[2018-11-04 17:43:49.012345] usb 1-1: new device\n",
+        ))
+        .stderr("");
+
+    // Without --boot-time, the boot time of this machine is used with a warning
+    if cfg!(target_os = "linux") {
+        let output = tztail().arg("--uptime").arg("/dev/null").output().unwrap();
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .starts_with("tztail: using the boot time of this machine"));
+    }
+
+    // A file that cannot be searched for an anchor line is reported
+    let path = env::temp_dir().join(format!("tztail_test_uptime_{}.log", std::process::id()));
+    fs::write(&path, b"[    1.500000] \xff\n").unwrap();
+    let output = tztail()
+        .arg("--boot-time")
+        .arg("anchor")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(
+        String::from_utf8_lossy(&output.stderr).starts_with(&format!(
+            "tztail: '{}' could not be searched for an anchor line",
+            path.display()
+        ))
+    );
+    fs::remove_file(&path).unwrap();
+}